@@ -0,0 +1,317 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, AttributeArgs, Error, FnArg, GenericArgument, Ident, ItemTrait, Lit, Meta,
+    NestedMeta, Pat, PathArguments, ReturnType, TraitItem, TraitItemMethod, Type,
+};
+
+/// 以trait声明一组RPC方法，同时生成服务端注册与类型化的客户端
+///
+/// ```ignore
+/// #[rpc(namespace = "currency")]
+/// pub trait CurrencyApi {
+///     #[rpc(name = "ids.detail")]
+///     async fn ids_detail(&self, param: GetDetailParam) -> Result<Vec<CurrencyDetail>, ExampleError>;
+/// }
+/// ```
+///
+/// - 方法名为 `namespace.方法名`，方法名默认为函数名，可用 `#[rpc(name = "..")]` 指定
+/// - 方法必须为 `async fn`，第一个参数为 `&self`，返回 `Result<T, E>`
+/// - trait增加 `into_route(self, route)`，将所有方法注册到 `Route`；
+///   实现中的 `async fn` 返回的Future需要是 `Send`
+/// - 生成 `<Trait>Client`，包装 `WsClient`，同名方法返回 `Result<T, RpcError>`；
+///   只在服务端使用时可用 `#[rpc(client = false)]` 不生成，此时不需要依赖 `jsonrpc-websocket`
+/// - 参数按位置以数组发送，服务端同样只按位置解析，单个参数也不会展开
+#[proc_macro_attribute]
+pub fn rpc(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let item = parse_macro_input!(item as ItemTrait);
+
+    expand(args, item)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+struct RpcMethod {
+    ident: Ident,
+    name: String,
+    docs: Vec<syn::Attribute>,
+    params: Vec<(Ident, Type)>,
+    output: Type,
+    result: Type,
+}
+
+fn expand(args: AttributeArgs, mut item: ItemTrait) -> Result<TokenStream2, Error> {
+    check_options(&args, &["namespace", "client"])?;
+    let namespace = string_option(&args, "namespace")?;
+    let client = bool_option(&args, "client")?.unwrap_or(true);
+
+    let mut methods = Vec::new();
+    for trait_item in item.items.iter_mut() {
+        match trait_item {
+            TraitItem::Method(method) => methods.push(parse_method(method, &namespace)?),
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "rpc trait can only contain methods",
+                ))
+            }
+        }
+    }
+
+    // 方法改写为返回Send的Future，便于注册到Route
+    for (trait_item, method) in item.items.iter_mut().zip(methods.iter()) {
+        let ident = &method.ident;
+        let output = &method.output;
+        let docs = &method.docs;
+        let params = method.params.iter().map(|(name, ty)| quote!(#name: #ty));
+        *trait_item = syn::parse_quote! {
+            #(#docs)*
+            fn #ident(&self, #(#params),*)
+                -> impl ::std::future::Future<Output = #output> + ::std::marker::Send;
+        };
+    }
+
+    let registrations = methods.iter().map(|method| {
+        let ident = &method.ident;
+        let name = &method.name;
+        let names: Vec<_> = method.params.iter().map(|(name, _)| name).collect();
+        let types: Vec<_> = method.params.iter().map(|(_, ty)| ty).collect();
+        let extract = match names.len() {
+            0 => quote!(),
+            // 单个参数同样按1元组解析，与客户端发送的数组一致，避免参数本身为数组时被误拆
+            _ => quote! {
+                ::jsonrpc_core::Params((#(#names,)*)): ::jsonrpc_core::Params<(#(#types,)*)>
+            },
+        };
+        quote! {
+            let route = {
+                let api = api.clone();
+                route.to(#name.to_string(), move |#extract| {
+                    let api = api.clone();
+                    async move { Self::#ident(&api, #(#names),*).await }
+                })
+            };
+        }
+    });
+    item.items.push(syn::parse_quote! {
+        /// 将所有方法注册到 `Route`
+        fn into_route(self, route: ::jsonrpc_core::route::Route) -> ::jsonrpc_core::route::Route
+        where
+            Self: Sized + ::std::marker::Send + ::std::marker::Sync + 'static,
+        {
+            let api = ::std::sync::Arc::new(self);
+            #(#registrations)*
+            route
+        }
+    });
+
+    if !client {
+        return Ok(quote!(#item));
+    }
+
+    let vis = &item.vis;
+    let client = format_ident!("{}Client", item.ident);
+    let client_doc = format!("`{}` 的客户端", item.ident);
+    let calls = methods.iter().map(|method| {
+        let ident = &method.ident;
+        let name = &method.name;
+        let docs = &method.docs;
+        let result = &method.result;
+        let names: Vec<_> = method.params.iter().map(|(name, _)| name).collect();
+        let params = method.params.iter().map(|(name, ty)| quote!(#name: #ty));
+        quote! {
+            #(#docs)*
+            #vis async fn #ident(&self, #(#params),*)
+                -> ::std::result::Result<#result, ::jsonrpc_websocket::RpcError>
+            {
+                self.client.call(#name, (#(#names,)*)).await
+            }
+        }
+    });
+
+    Ok(quote! {
+        #item
+
+        #[doc = #client_doc]
+        #[derive(Clone)]
+        #vis struct #client {
+            client: ::jsonrpc_websocket::WsClient,
+        }
+
+        #[allow(dead_code)]
+        impl #client {
+            #vis fn new(client: ::jsonrpc_websocket::WsClient) -> Self {
+                Self { client }
+            }
+
+            #vis fn client(&self) -> &::jsonrpc_websocket::WsClient {
+                &self.client
+            }
+
+            #(#calls)*
+        }
+    })
+}
+
+fn parse_method(
+    method: &mut TraitItemMethod,
+    namespace: &Option<String>,
+) -> Result<RpcMethod, Error> {
+    let sig = &method.sig;
+    if sig.asyncness.is_none() {
+        return Err(Error::new(sig.span(), "rpc method must be async"));
+    }
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "rpc method can not be generic",
+        ));
+    }
+    if let Some(body) = &method.default {
+        return Err(Error::new(
+            body.span(),
+            "rpc method can not have a default body",
+        ));
+    }
+
+    // 取出方法上的 #[rpc(..)]，其余属性(如文档)保留
+    let mut name = None;
+    let mut docs = Vec::new();
+    for attr in method.attrs.drain(..) {
+        if !attr.path.is_ident("rpc") {
+            docs.push(attr);
+            continue;
+        }
+        let args = match attr.parse_meta()? {
+            Meta::List(list) => list.nested.into_iter().collect::<Vec<_>>(),
+            meta => return Err(Error::new(meta.span(), "expect #[rpc(name = \"..\")]")),
+        };
+        check_options(&args, &["name"])?;
+        name = string_option(&args, "name")?;
+    }
+    let name = name.unwrap_or_else(|| sig.ident.to_string());
+    let name = match namespace {
+        Some(namespace) => format!("{}.{}", namespace, name),
+        None => name,
+    };
+
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(receiver))
+            if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+        _ => return Err(Error::new(sig.span(), "rpc method must take &self")),
+    }
+
+    let mut params = Vec::new();
+    for input in inputs {
+        match input {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) => params.push((pat.ident.clone(), (*arg.ty).clone())),
+                pat => {
+                    return Err(Error::new(
+                        pat.span(),
+                        "rpc method param must be an identifier",
+                    ))
+                }
+            },
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(receiver.span(), "unexpected receiver"))
+            }
+        }
+    }
+
+    let output = match &sig.output {
+        ReturnType::Type(_, ty) => (**ty).clone(),
+        ReturnType::Default => {
+            return Err(Error::new(
+                sig.span(),
+                "rpc method must return Result<T, E>",
+            ))
+        }
+    };
+    let result = result_type(&output)
+        .ok_or_else(|| Error::new(output.span(), "rpc method must return Result<T, E>"))?;
+
+    Ok(RpcMethod {
+        ident: sig.ident.clone(),
+        name,
+        docs,
+        params,
+        output,
+        result,
+    })
+}
+
+/// `Result<T, E>` 中的T
+fn result_type(ty: &Type) -> Option<Type> {
+    let path = match ty {
+        Type::Path(path) => &path.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let args = match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 2 => &args.args,
+        _ => return None,
+    };
+    match args.first()? {
+        GenericArgument::Type(ty) => Some(ty.clone()),
+        _ => None,
+    }
+}
+
+/// 检查参数均为 `key = 字面量` 形式，且key在keys之中
+fn check_options(args: &[NestedMeta], keys: &[&str]) -> Result<(), Error> {
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv))
+                if keys.iter().any(|key| nv.path.is_ident(key)) => {}
+            arg => {
+                let expect: Vec<_> = keys.iter().map(|key| format!("{} = ..", key)).collect();
+                return Err(Error::new(
+                    arg.span(),
+                    format!("expect {}", expect.join(", ")),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 取出 `key = "value"` 形式的参数
+fn string_option(args: &[NestedMeta], key: &str) -> Result<Option<String>, Error> {
+    let mut value = None;
+    for lit in option_lits(args, key) {
+        match lit {
+            Lit::Str(lit) => value = Some(lit.value()),
+            lit => return Err(Error::new(lit.span(), "expect a string")),
+        }
+    }
+    Ok(value)
+}
+
+/// 取出 `key = true|false` 形式的参数
+fn bool_option(args: &[NestedMeta], key: &str) -> Result<Option<bool>, Error> {
+    let mut value = None;
+    for lit in option_lits(args, key) {
+        match lit {
+            Lit::Bool(lit) => value = Some(lit.value),
+            lit => return Err(Error::new(lit.span(), "expect true or false")),
+        }
+    }
+    Ok(value)
+}
+
+fn option_lits<'a>(args: &'a [NestedMeta], key: &'a str) -> impl Iterator<Item = &'a Lit> {
+    args.iter().filter_map(move |arg| match arg {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident(key) => Some(&nv.lit),
+        _ => None,
+    })
+}
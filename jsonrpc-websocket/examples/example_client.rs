@@ -1,135 +1,25 @@
-use futures_util::stream::{SplitSink, SplitStream};
-use futures_util::{SinkExt, StreamExt};
+use jsonrpc_websocket::WsClient;
+use serde_json::Value;
 use std::env;
-use std::{
-    sync::{Arc, Mutex},
-    time::Duration,
-};
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::net::TcpStream;
-use tokio::time;
-use tokio_tungstenite::connect_async;
-use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::WebSocketStream;
-use url::Url;
 
-static LOCAL_SERVER: &'static str = "ws://127.0.0.1:9000";
+static LOCAL_SERVER: &str = "ws://127.0.0.1:9000";
 
-const RECONN_INTERVAL: u64 = 3000;
-
-struct WebSockWriteHalf(pub Option<SplitSink<WebSocketStream<TcpStream>, Message>>);
-struct WebSockReadHalf(pub Option<SplitStream<WebSocketStream<TcpStream>>>);
-
-async fn set_conn_none(
-    lock_ws_receiver: Arc<Mutex<WebSockReadHalf>>,
-    lock_ws_sender: Arc<Mutex<WebSockWriteHalf>>,
-) -> bool {
-    let mut ws_receiver = lock_ws_receiver.lock().unwrap();
-    let mut ws_sender = lock_ws_sender.lock().unwrap();
-
-    ws_receiver.0 = None;
-    ws_sender.0 = None;
-    return true;
-}
-
-async fn client_check_conn(
-    case_url: Url,
-    lock_ws_receiver: Arc<Mutex<WebSockReadHalf>>,
-    lock_ws_sender: Arc<Mutex<WebSockWriteHalf>>,
-) -> bool {
-    let ws_receiver = lock_ws_receiver.lock().unwrap();
-
-    if let None = ws_receiver.0 {
-        drop(ws_receiver);
-
-        if let Ok((ws_stream, _)) = connect_async(case_url).await {
-            let (sender, receiver) = ws_stream.split();
-            let mut ws_receiver = lock_ws_receiver.lock().unwrap();
-            let mut ws_sender = lock_ws_sender.lock().unwrap();
-
-            ws_sender.0 = Some(sender);
-            ws_receiver.0 = Some(receiver);
-            log::info!("connect success");
-            return true;
-        } else {
-            log::info!("connect fail, reconning ...");
-            return false;
-        }
-    }
-    return true;
-}
-
-async fn receiver_loop(
-    case_url: Url,
-    lock_ws_receiver: Arc<Mutex<WebSockReadHalf>>,
-    lock_ws_sender: Arc<Mutex<WebSockWriteHalf>>,
-) {
-    loop {
-        let mut ws_receiver = lock_ws_receiver.lock().unwrap();
-
-        let result: Result<String, bool> = match &mut ws_receiver.0 {
-            Some(ws_receiver) => match ws_receiver.next().await {
-                Some(Ok(msg)) => {
-                    if msg.is_text() {
-                        Ok(msg.into_text().unwrap())
-                    } else {
-                        log::warn!("Peer receive data format error, not text");
-                        Err(false)
-                    }
-                }
-                Some(Err(_)) => {
-                    log::warn!("server close connect");
-                    Err(true)
-                }
-                None => Err(true),
-            },
-            None => Err(true),
-        };
-        drop(ws_receiver);
-
-        match result {
-            Ok(msg) => {
-                println!("resp: {}", msg);
-            }
-            Err(is_reconn) => {
-                if is_reconn {
-                    set_conn_none(lock_ws_receiver.clone(), lock_ws_sender.clone()).await;
-                    if client_check_conn(
-                        case_url.clone(),
-                        lock_ws_receiver.clone(),
-                        lock_ws_sender.clone(),
-                    )
-                    .await
-                    {
-                        log::info!("re_conn: {}", case_url);
-                        continue;
-                    } else {
-                        time::delay_for(Duration::from_millis(RECONN_INTERVAL)).await;
-                    }
-                }
-            }
-        }
-    }
-}
-
-async fn ws_send(str_cmd: String, lock_ws_sender: Arc<Mutex<WebSockWriteHalf>>) {
-    let mut ws_sender = match lock_ws_sender.try_lock() {
-        Ok(ws_sender) => ws_sender,
-        Err(_) => {
-            time::delay_for(Duration::from_millis(100)).await;
-
-            log::warn!("ws_stream close, skip send");
-            return;
-        }
+// 每行一个调用：方法名及可选的JSON参数，如
+//   currency.ids.detail {"ids": ["BTC"]}
+fn parse_line(line: &str) -> Result<(&str, Value), String> {
+    let line = line.trim();
+    let (method, params) = match line.find(char::is_whitespace) {
+        Some(pos) => (&line[..pos], line[pos..].trim()),
+        None => (line, ""),
     };
 
-    if let Some(ws_sender) = &mut ws_sender.0 {
-        if let Err(err) = ws_sender.send(Message::Text(str_cmd)).await {
-            log::warn!("ws_stream send failed with err: {}", err);
-        }
+    let params = if params.is_empty() {
+        Value::Null
     } else {
-        log::warn!("ws_stream close, skip send");
-    }
+        serde_json::from_str(params).map_err(|err| format!("params format error: {}", err))?
+    };
+    Ok((method, params))
 }
 
 #[tokio::main]
@@ -141,28 +31,30 @@ async fn main() {
         .nth(1)
         .unwrap_or_else(|| LOCAL_SERVER.to_string());
 
-    let case_url = Url::parse(&connect_transport).expect("Bad testcase URL");
+    let client = match WsClient::connect(&connect_transport).await {
+        Ok(client) => client,
+        Err(err) => panic!("{}", err),
+    };
 
-    let lock_ws_receiver = Arc::new(Mutex::new(WebSockReadHalf(None)));
-    let lock_ws_sender = Arc::new(Mutex::new(WebSockWriteHalf(None)));
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
 
-    let local = tokio::task::LocalSet::new();
-    local
-        .run_until(async move {
-            tokio::task::spawn_local(receiver_loop(
-                case_url,
-                lock_ws_receiver.clone(),
-                lock_ws_sender.clone(),
-            ));
+        let (method, params) = match parse_line(&line) {
+            Ok(call) => call,
+            Err(err) => {
+                println!("{}", err);
+                continue;
+            }
+        };
 
-            let mut reader = BufReader::new(tokio::io::stdin());
-            loop {
-                let mut str_cmd = String::new();
-                reader.read_line(&mut str_cmd).await.unwrap();
-                str_cmd.pop();
+        match client.call::<_, Value>(method, params).await {
+            Ok(result) => println!("result: {}", result),
+            Err(err) => println!("error: {}", err),
+        }
+    }
 
-                ws_send(str_cmd, lock_ws_sender.clone()).await;
-            }
-        })
-        .await;
+    client.close();
 }
@@ -1,7 +1,7 @@
 extern crate jsonrpc_websocket;
 
 use jsonrpc_core::route::Route;
-use jsonrpc_core::Data;
+use jsonrpc_core::{Data, Params};
 use jsonrpc_lite::Error as JsonRpcError;
 use jsonrpc_websocket::WsServer;
 use serde::{Deserialize, Serialize};
@@ -14,9 +14,9 @@ pub enum ExampleError {
     ParamIsNone,
 }
 
-impl Into<JsonRpcError> for ExampleError {
-    fn into(self) -> JsonRpcError {
-        let (code, message) = match self {
+impl From<ExampleError> for JsonRpcError {
+    fn from(err: ExampleError) -> JsonRpcError {
+        let (code, message) = match err {
             ExampleError::ParamIsNone => (1000i64, "Param is none"),
         };
 
@@ -37,13 +37,14 @@ pub struct CurrencyDetail {
     owner: String,
 }
 
+#[derive(Default)]
 pub struct CurrencyStore {}
 impl CurrencyStore {
     pub fn get_detail_by_ids(
         &self,
         req: GetDetailParam,
     ) -> Result<Vec<CurrencyDetail>, ExampleError> {
-        if req.ids.len() == 0 {
+        if req.ids.is_empty() {
             return Err(ExampleError::ParamIsNone);
         }
         Ok(Vec::<CurrencyDetail>::new())
@@ -57,21 +58,20 @@ pub struct GetDetailParam {
 
 pub async fn get_detail_by_ids(
     wallet: Data<TSystem>,
-    req: GetDetailParam,
+    Params(req): Params<GetDetailParam>,
 ) -> Result<Vec<CurrencyDetail>, ExampleError> {
     let store = wallet.get_ref().store.try_read().unwrap();
     store.get_detail_by_ids(req)
 }
 
+#[derive(Default)]
 pub struct TSystem {
     pub store: RwLock<CurrencyStore>,
 }
 
 impl TSystem {
     pub fn new() -> Self {
-        Self {
-            store: RwLock::new(CurrencyStore {}),
-        }
+        Self::default()
     }
 }
 
@@ -79,7 +79,9 @@ pub async fn start_ws_server(bind_transport: String) {
     let route: Arc<Route> = Arc::new(
         Route::new()
             .data(TSystem::new())
-            .to("currency.ids.detail".to_string(), get_detail_by_ids),
+            .to("currency.ids.detail".to_string(), get_detail_by_ids)
+            .build()
+            .unwrap(),
     );
 
     let ws_server = match WsServer::bind(bind_transport).await {
@@ -90,7 +92,7 @@ pub async fn start_ws_server(bind_transport: String) {
     ws_server.listen_loop(route).await;
 }
 
-static LOCAL_SERVER: &'static str = "127.0.0.1:9000";
+static LOCAL_SERVER: &str = "127.0.0.1:9000";
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,836 @@
+use futures_channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures_channel::oneshot;
+use futures_util::stream::{self, Stream};
+use futures_util::{SinkExt, StreamExt};
+use jsonrpc_core::route::request_message;
+use jsonrpc_core::{CallError, Id};
+use jsonrpc_lite::Error as JsonRpcError;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::time;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, WebSocketStream};
+use url::Url;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+type WsStream = WebSocketStream<TcpStream>;
+
+/// 客户端调用错误：服务端错误响应、超时、连接断开或参数/结果格式错误
+pub type RpcError = CallError;
+
+/// 连接状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// 连接断开，正在重连
+    Reconnecting,
+    /// 已关闭(主动关闭、未启用重连或重连次数用尽)，不会再恢复
+    Closed,
+}
+
+/// 连接断开时对调用的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InFlightPolicy {
+    /// 等待中的调用立即失败，重连期间的新调用也立即失败
+    Fail,
+    /// 已发出的调用失败，未发出及重连期间的新调用在重连后发送
+    Wait,
+    /// 同Wait，且已发出的幂等调用(`WsClient::call_idempotent`)在重连后重发
+    Replay,
+}
+
+/// 重连的指数退避策略
+///   第n次重连前等待 `min(initial * multiplier^n, max)`，并随机浮动 `±jitter` 比例
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    multiplier: f64,
+    jitter: f64,
+    max_retries: Option<u32>,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_retries: None,
+        }
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// 随机浮动比例，取值0~1，0表示不浮动
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// 连续重连失败的最大次数，超过后客户端关闭
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let base = self.initial.as_secs_f64() * self.multiplier.powi(attempt as i32);
+        let base = base.min(self.max.as_secs_f64());
+        let factor = if self.jitter > 0.0 {
+            rand::thread_rng().gen_range(1.0 - self.jitter, 1.0 + self.jitter)
+        } else {
+            1.0
+        };
+        Duration::from_secs_f64(base * factor)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100), Duration::from_secs(30))
+    }
+}
+
+/// WebSocket客户端
+///   可以被多个任务共享(Clone)，同时发出的请求按id对应各自的响应
+#[derive(Clone)]
+pub struct WsClient {
+    shared: Arc<Shared>,
+    _guard: Arc<CloseGuard>,
+}
+
+// 最后一个WsClient释放时关闭连接
+struct CloseGuard(Arc<Shared>);
+
+impl Drop for CloseGuard {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// 连接前的客户端配置
+pub struct WsClientBuilder {
+    url: String,
+    request_timeout: Duration,
+    reconnect: Option<Backoff>,
+    policy: InFlightPolicy,
+}
+
+impl WsClientBuilder {
+    /// 设置请求等待响应的超时时间，包括等待重连的时间
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// 连接断开后按退避策略自动重连，默认不重连
+    pub fn reconnect(mut self, backoff: Backoff) -> Self {
+        self.reconnect = Some(backoff);
+        self
+    }
+
+    /// 设置重连时对调用的处理方式，默认 `InFlightPolicy::Fail`
+    pub fn in_flight_policy(mut self, policy: InFlightPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// 建立首次连接，首次连接失败直接返回错误
+    pub async fn connect(self) -> Result<WsClient, String> {
+        let url = Url::parse(&self.url).map_err(|err| err.to_string())?;
+        let (ws_stream, _) = connect_async(url.clone())
+            .await
+            .map_err(|err| format!("connect {} error, with info: {}", self.url, err))?;
+        log::info!("connect {} success", self.url);
+
+        let (queue_in, queue_out) = mpsc::unbounded();
+        let (state_in, state_out) = watch::channel(ConnectionState::Connected);
+        let shared = Arc::new(Shared {
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            subscriptions: Mutex::new(Subscriptions::default()),
+            queue: queue_in,
+            state_in,
+            state_out,
+            request_timeout: self.request_timeout,
+            policy: self.policy,
+        });
+
+        tokio::spawn(Shared::run(
+            shared.clone(),
+            ws_stream,
+            queue_out,
+            url,
+            self.reconnect,
+        ));
+
+        Ok(WsClient {
+            _guard: Arc::new(CloseGuard(shared.clone())),
+            shared,
+        })
+    }
+}
+
+impl WsClient {
+    pub fn builder(url: &str) -> WsClientBuilder {
+        WsClientBuilder {
+            url: url.to_string(),
+            request_timeout: REQUEST_TIMEOUT,
+            reconnect: None,
+            policy: InFlightPolicy::Fail,
+        }
+    }
+
+    /// 使用默认配置连接服务端
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        Self::builder(url).connect().await
+    }
+
+    /// 调用服务端方法并等待结果，params为 `()` 时省略params
+    pub async fn call<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, RpcError> {
+        self.request(method, params, false).await
+    }
+
+    /// 调用幂等的方法，`InFlightPolicy::Replay` 时断开前已发出的调用在重连后重发
+    pub async fn call_idempotent<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, RpcError> {
+        self.request(method, params, true).await
+    }
+
+    /// 订阅服务端推送，返回的流产出每条推送的result
+    ///   流在连接断开(包括重连)时产出 `RpcError::Closed` 后结束，释放时调用unsubscribe方法取消订阅
+    pub async fn subscribe<P: Serialize, T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+        unsubscribe: &str,
+    ) -> Result<Subscription<T>, RpcError> {
+        let (notify_in, notify_out) = mpsc::unbounded();
+        let id = self
+            .request_value(method, params, false, Some(notify_in))
+            .await?;
+
+        Ok(Subscription {
+            id,
+            unsubscribe: unsubscribe.to_string(),
+            notify_out,
+            shared: self.shared.clone(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// 发送通知，服务端不会响应
+    pub fn notify<P: Serialize>(&self, method: &str, params: P) -> Result<(), RpcError> {
+        let params =
+            serde_json::to_value(params).map_err(|err| CallError::Parse(err.to_string()))?;
+        self.shared.check_state()?;
+        self.shared.enqueue(Outgoing {
+            ids: Vec::new(),
+            msg: request_message(method, params, None).to_string(),
+        })
+    }
+
+    /// 关闭连接，等待中的请求返回 `RpcError::Closed`
+    pub fn close(&self) {
+        self.shared.close();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state() == ConnectionState::Closed
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        *self.shared.state_out.borrow()
+    }
+
+    /// 连接状态的变化，首先产出当前状态，关闭后结束
+    pub fn state_changes(&self) -> impl Stream<Item = ConnectionState> + Unpin {
+        let state_out = self.shared.state_out.clone();
+        Box::pin(stream::unfold(
+            (state_out, false),
+            |(mut state_out, closed)| async move {
+                if closed {
+                    return None;
+                }
+                let state = state_out.recv().await?;
+                Some((state, (state_out, state == ConnectionState::Closed)))
+            },
+        ))
+    }
+
+    async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+        idempotent: bool,
+    ) -> Result<R, RpcError> {
+        let result = self.request_value(method, params, idempotent, None).await?;
+        serde_json::from_value(result).map_err(|err| CallError::Parse(err.to_string()))
+    }
+
+    async fn request_value<P: Serialize>(
+        &self,
+        method: &str,
+        params: P,
+        idempotent: bool,
+        subscription: Option<NotifySender>,
+    ) -> Result<Value, RpcError> {
+        let params =
+            serde_json::to_value(params).map_err(|err| CallError::Parse(err.to_string()))?;
+        let id = self.shared.next_id.fetch_add(1, Ordering::SeqCst);
+        let msg = request_message(method, params, Some(&Id::from(id))).to_string();
+
+        let resp_out = self
+            .shared
+            .register(id, msg.clone(), idempotent, subscription)?;
+        self.shared.enqueue(Outgoing { ids: vec![id], msg })?;
+
+        self.shared.wait(id, resp_out).await
+    }
+
+    /// 创建批量调用
+    pub fn batch(&self) -> Batch {
+        Batch {
+            shared: self.shared.clone(),
+            calls: Vec::new(),
+            msgs: Vec::new(),
+        }
+    }
+}
+
+/// 批量调用，`send` 时作为一个JSON数组发送，各调用的结果按id分别返回
+///   未发送即释放时，其中的调用返回 `RpcError::Closed`
+pub struct Batch {
+    shared: Arc<Shared>,
+    calls: Vec<(u64, String, RespSender)>,
+    msgs: Vec<Value>,
+}
+
+impl Batch {
+    /// 加入一个调用，返回的Future在 `send` 之后等待该调用的结果
+    pub fn call<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: P,
+    ) -> impl Future<Output = Result<R, RpcError>> {
+        let shared = self.shared.clone();
+        let queued = serde_json::to_value(params)
+            .map_err(|err| CallError::Parse(err.to_string()))
+            .map(|params| {
+                let id = shared.next_id.fetch_add(1, Ordering::SeqCst);
+                let msg = request_message(method, params, Some(&Id::from(id)));
+                let (resp_in, resp_out) = oneshot::channel();
+                self.calls.push((id, msg.to_string(), resp_in));
+                self.msgs.push(msg);
+                (id, resp_out)
+            });
+
+        async move {
+            let (id, resp_out) = queued?;
+            let result = shared.wait(id, resp_out).await?;
+            serde_json::from_value(result).map_err(|err| CallError::Parse(err.to_string()))
+        }
+    }
+
+    /// 加入一个通知
+    pub fn notify<P: Serialize>(&mut self, method: &str, params: P) -> Result<(), RpcError> {
+        let params =
+            serde_json::to_value(params).map_err(|err| CallError::Parse(err.to_string()))?;
+        self.msgs.push(request_message(method, params, None));
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.msgs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.msgs.is_empty()
+    }
+
+    /// 作为一帧发送，空的批量调用不发送
+    pub fn send(self) -> Result<(), RpcError> {
+        if self.msgs.is_empty() {
+            return Ok(());
+        }
+        self.shared.check_state()?;
+
+        let mut ids = Vec::with_capacity(self.calls.len());
+        {
+            let mut pending = self.shared.pending.lock().unwrap();
+            for (id, msg, resp_in) in self.calls {
+                pending.insert(
+                    id,
+                    Pending {
+                        resp_in,
+                        msg,
+                        idempotent: false,
+                        sent: false,
+                        subscription: None,
+                    },
+                );
+                ids.push(id);
+            }
+        }
+
+        self.shared.enqueue(Outgoing {
+            ids,
+            msg: Value::Array(self.msgs).to_string(),
+        })
+    }
+}
+
+type NotifySender = UnboundedSender<Result<Value, RpcError>>;
+
+/// 订阅推送的流，由 `WsClient::subscribe` 返回
+pub struct Subscription<T> {
+    id: Value,
+    unsubscribe: String,
+    notify_out: UnboundedReceiver<Result<Value, RpcError>>,
+    shared: Arc<Shared>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Subscription<T> {
+    /// 服务端返回的订阅id
+    pub fn id(&self) -> &Value {
+        &self.id
+    }
+}
+
+impl<T: DeserializeOwned> Stream for Subscription<T> {
+    type Item = Result<T, RpcError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.notify_out.poll_next_unpin(cx).map(|item| {
+            item.map(|result| {
+                result.and_then(|value| {
+                    serde_json::from_value(value).map_err(|err| CallError::Parse(err.to_string()))
+                })
+            })
+        })
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.shared.unsubscribe(&self.unsubscribe, &self.id);
+    }
+}
+
+// 等待响应的调用
+type RespSender = oneshot::Sender<Result<Value, JsonRpcError>>;
+
+struct Pending {
+    resp_in: RespSender,
+    msg: String,
+    idempotent: bool,
+    // 是否已写入连接
+    sent: bool,
+    // 订阅调用，成功后以返回的订阅id接收推送
+    subscription: Option<NotifySender>,
+}
+
+// 待发送的消息及其中包含的请求id
+struct Outgoing {
+    ids: Vec<u64>,
+    msg: String,
+}
+
+#[derive(Default)]
+struct Subscriptions {
+    active: HashMap<String, NotifySender>,
+    // 订阅响应之前到达的推送，订阅调用完成后转交
+    early: HashMap<String, Vec<Value>>,
+}
+
+struct Shared {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, Pending>>,
+    subscriptions: Mutex<Subscriptions>,
+    queue: UnboundedSender<Outgoing>,
+    state_in: watch::Sender<ConnectionState>,
+    state_out: watch::Receiver<ConnectionState>,
+    request_timeout: Duration,
+    policy: InFlightPolicy,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: Id,
+    result: Option<Value>,
+    error: Option<JsonRpcError>,
+}
+
+impl Shared {
+    /// 连接管理：服务当前连接，断开后按策略处理调用并重连
+    async fn run(
+        shared: Arc<Shared>,
+        mut ws_stream: WsStream,
+        mut queue: UnboundedReceiver<Outgoing>,
+        url: Url,
+        reconnect: Option<Backoff>,
+    ) {
+        let mut replays = Vec::new();
+        loop {
+            if shared.serve(ws_stream, &mut queue, replays).await {
+                break;
+            }
+
+            let backoff = match &reconnect {
+                Some(backoff) => backoff,
+                None => break,
+            };
+
+            log::warn!("disconnect from {}, reconnecting", url);
+            shared.set_state(ConnectionState::Reconnecting);
+            replays = shared.on_disconnect(&mut queue);
+
+            ws_stream = match shared.reconnect(&url, backoff).await {
+                Some(ws_stream) => ws_stream,
+                None => break,
+            };
+            log::info!("reconnect {} success", url);
+            shared.set_state(ConnectionState::Connected);
+        }
+
+        shared.close();
+    }
+
+    /// 服务一个连接直到断开，客户端关闭时返回true
+    async fn serve(
+        &self,
+        ws_stream: WsStream,
+        queue: &mut UnboundedReceiver<Outgoing>,
+        replays: Vec<Outgoing>,
+    ) -> bool {
+        let (mut write_half, mut read_half) = ws_stream.split();
+
+        for item in replays {
+            self.mark_sent(&item.ids);
+            if write_half.send(Message::Text(item.msg)).await.is_err() {
+                return false;
+            }
+        }
+
+        loop {
+            tokio::select! {
+                item = queue.next() => match item {
+                    Some(item) => {
+                        self.mark_sent(&item.ids);
+                        if write_half.send(Message::Text(item.msg)).await.is_err() {
+                            return false;
+                        }
+                    }
+                    None => {
+                        let _ = write_half.send(Message::Close(None)).await;
+                        return true;
+                    }
+                },
+                msg = read_half.next() => match msg {
+                    Some(Ok(Message::Text(msg_str))) => self.handle_message(&msg_str),
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return false,
+                    Some(Ok(_)) => log::debug!("data format not String, ignore this item"),
+                },
+            }
+        }
+    }
+
+    async fn reconnect(&self, url: &Url, backoff: &Backoff) -> Option<WsStream> {
+        let mut attempt = 0;
+        loop {
+            if self.queue.is_closed() {
+                return None;
+            }
+            if backoff
+                .max_retries
+                .is_some_and(|max_retries| attempt >= max_retries)
+            {
+                log::warn!("reconnect {} fail after {} retries, close", url, attempt);
+                return None;
+            }
+
+            time::delay_for(backoff.delay(attempt)).await;
+            attempt += 1;
+
+            match connect_async(url.clone()).await {
+                Ok((ws_stream, _)) => return Some(ws_stream),
+                Err(err) => log::warn!("reconnect {} fail, with info: {}", url, err),
+            }
+        }
+    }
+
+    /// 连接断开时按策略处理等待中的调用，返回重连后需要重发的消息
+    fn on_disconnect(&self, queue: &mut UnboundedReceiver<Outgoing>) -> Vec<Outgoing> {
+        // 服务端的订阅随连接断开取消
+        self.close_subscriptions();
+
+        let mut pending = self.pending.lock().unwrap();
+        let mut replays = Vec::new();
+
+        match self.policy {
+            InFlightPolicy::Fail => {
+                pending.clear();
+                // 丢弃未发出的消息
+                while queue.try_recv().is_ok() {}
+            }
+            InFlightPolicy::Wait | InFlightPolicy::Replay => {
+                let replay = self.policy == InFlightPolicy::Replay;
+                pending.retain(|id, call| {
+                    if !call.sent {
+                        return true;
+                    }
+                    if replay && call.idempotent {
+                        replays.push(Outgoing {
+                            ids: vec![*id],
+                            msg: call.msg.clone(),
+                        });
+                        return true;
+                    }
+                    false
+                });
+            }
+        }
+        replays
+    }
+
+    fn register(
+        &self,
+        id: u64,
+        msg: String,
+        idempotent: bool,
+        subscription: Option<NotifySender>,
+    ) -> Result<oneshot::Receiver<Result<Value, JsonRpcError>>, RpcError> {
+        self.check_state()?;
+
+        let (resp_in, resp_out) = oneshot::channel();
+        self.pending.lock().unwrap().insert(
+            id,
+            Pending {
+                resp_in,
+                msg,
+                idempotent,
+                sent: false,
+                subscription,
+            },
+        );
+        Ok(resp_out)
+    }
+
+    /// 等待调用的响应，超时后不再等待
+    async fn wait(
+        &self,
+        id: u64,
+        resp_out: oneshot::Receiver<Result<Value, JsonRpcError>>,
+    ) -> Result<Value, RpcError> {
+        let output = match time::timeout(self.request_timeout, resp_out).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(_)) => return Err(CallError::Closed),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                return Err(CallError::Timeout);
+            }
+        };
+
+        output.map_err(CallError::Rpc)
+    }
+
+    fn check_state(&self) -> Result<(), RpcError> {
+        match *self.state_out.borrow() {
+            ConnectionState::Connected => Ok(()),
+            ConnectionState::Reconnecting if self.policy != InFlightPolicy::Fail => Ok(()),
+            _ => Err(CallError::Closed),
+        }
+    }
+
+    fn enqueue(&self, item: Outgoing) -> Result<(), RpcError> {
+        let ids = item.ids.clone();
+        self.queue.unbounded_send(item).map_err(|_| {
+            let mut pending = self.pending.lock().unwrap();
+            for id in ids {
+                pending.remove(&id);
+            }
+            CallError::Closed
+        })
+    }
+
+    fn mark_sent(&self, ids: &[u64]) {
+        let mut pending = self.pending.lock().unwrap();
+        for id in ids {
+            if let Some(call) = pending.get_mut(id) {
+                call.sent = true;
+            }
+        }
+    }
+
+    /// 取消订阅，以通知发送，服务端不会响应也不需要记录等待中的调用；
+    ///   连接已断开时服务端的订阅已取消，不再发送
+    fn unsubscribe(&self, unsubscribe: &str, sub_id: &Value) {
+        let removed = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .active
+            .remove(&subscription_key(sub_id));
+        if removed.is_none() || *self.state_out.borrow() != ConnectionState::Connected {
+            return;
+        }
+
+        let msg = request_message(unsubscribe, json!([sub_id]), None).to_string();
+        let _ = self.enqueue(Outgoing {
+            ids: Vec::new(),
+            msg,
+        });
+    }
+
+    /// 推送交给对应订阅id的流
+    fn handle_notification(&self, params: &Value) {
+        let key = match params.get("subscription") {
+            Some(sub_id) => subscription_key(sub_id),
+            None => {
+                log::debug!("ignore server notification: {}", params);
+                return;
+            }
+        };
+        let result = params.get("result").cloned().unwrap_or(Value::Null);
+
+        let subscribing = self
+            .pending
+            .lock()
+            .unwrap()
+            .values()
+            .any(|call| call.subscription.is_some());
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        match subscriptions.active.get(&key) {
+            Some(notify_in) => {
+                let _ = notify_in.unbounded_send(Ok(result));
+            }
+            None if subscribing => subscriptions.early.entry(key).or_default().push(result),
+            None => log::debug!("subscription {} not found, ignore notification", key),
+        }
+    }
+
+    /// 订阅调用成功，开始接收该订阅id的推送
+    fn add_subscription(&self, sub_id: &Value, notify_in: NotifySender) {
+        let key = subscription_key(sub_id);
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        for result in subscriptions.early.remove(&key).unwrap_or_default() {
+            let _ = notify_in.unbounded_send(Ok(result));
+        }
+        subscriptions.active.insert(key, notify_in);
+    }
+
+    fn close_subscriptions(&self) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.early.clear();
+        for (_, notify_in) in subscriptions.active.drain() {
+            let _ = notify_in.unbounded_send(Err(CallError::Closed));
+        }
+    }
+
+    /// 处理服务端消息，响应(或批量响应)交给等待者，推送交给订阅流
+    fn handle_message(&self, msg_str: &str) {
+        let value: Value = match serde_json::from_str(msg_str) {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!("server message format error, with info: {}", err);
+                return;
+            }
+        };
+
+        let responses = match value {
+            Value::Array(array) => array,
+            value => vec![value],
+        };
+
+        for resp in responses {
+            if resp.get("method").is_some() {
+                if resp.get("id").is_some() {
+                    log::debug!("ignore server request: {}", resp);
+                } else {
+                    self.handle_notification(resp.get("params").unwrap_or(&Value::Null));
+                }
+                continue;
+            }
+
+            let resp: Response = match serde_json::from_value(resp) {
+                Ok(resp) => resp,
+                Err(err) => {
+                    log::warn!("server response format error, with info: {}", err);
+                    continue;
+                }
+            };
+
+            let output = match resp.error {
+                Some(err) => Err(err),
+                None => Ok(resp.result.unwrap_or(Value::Null)),
+            };
+
+            let call = match &resp.id {
+                Id::Number(id) => id
+                    .as_u64()
+                    .and_then(|id| self.pending.lock().unwrap().remove(&id)),
+                _ => None,
+            };
+            match call {
+                // 调用方已超时，忽略
+                Some(call) => {
+                    if let (Some(notify_in), Ok(sub_id)) = (call.subscription, &output) {
+                        // 订阅调用已被放弃时不再接收推送
+                        if !call.resp_in.is_canceled() {
+                            self.add_subscription(sub_id, notify_in);
+                        }
+                    }
+                    let _ = call.resp_in.send(output);
+                }
+                None => log::warn!("server response {} has no pending request, ignore", resp.id),
+            }
+        }
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        if *self.state_out.borrow() != state {
+            let _ = self.state_in.broadcast(state);
+        }
+    }
+
+    fn close(&self) {
+        self.set_state(ConnectionState::Closed);
+        self.queue.close_channel();
+        self.pending.lock().unwrap().clear();
+        self.close_subscriptions();
+    }
+}
+
+// 订阅id通常为字符串，其他类型按JSON文本比较
+fn subscription_key(sub_id: &Value) -> String {
+    match sub_id {
+        Value::String(sub_id) => sub_id.clone(),
+        sub_id => sub_id.to_string(),
+    }
+}
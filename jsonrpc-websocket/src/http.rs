@@ -0,0 +1,192 @@
+use crate::server::{ClientConfig, WsServer};
+use hyper::body::HttpBody;
+use hyper::header::{ALLOW, CONTENT_TYPE, UPGRADE};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use jsonrpc_core::route::{parse_error_response, route_jsonrpc_with_context, Route};
+use jsonrpc_core::Context;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, watch};
+use tokio::time;
+use tokio_tungstenite::tungstenite::handshake::server::create_response;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::WebSocketStream;
+
+/// 以HTTP方式服务一个连接：POST请求直接路由，WebSocket升级请求转为WebSocket连接
+pub(crate) async fn serve_connection<S>(
+    stream: S,
+    peer: SocketAddr,
+    route: Arc<Route>,
+    config: ClientConfig,
+    shutdown: watch::Receiver<bool>,
+    done: mpsc::Sender<()>,
+) -> Result<(), String>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // HTTP请求没有会话，不支持订阅及服务端请求
+    let context = Context::new().with(peer);
+
+    let service = {
+        let shutdown = shutdown.clone();
+        service_fn(move |req| {
+            handle_request(
+                req,
+                peer,
+                route.clone(),
+                context.clone(),
+                config,
+                shutdown.clone(),
+                done.clone(),
+            )
+        })
+    };
+
+    let conn = Http::new()
+        .serve_connection(stream, service)
+        .with_upgrades();
+    tokio::pin!(conn);
+
+    tokio::select! {
+        result = &mut conn => {
+            return result.map_err(|err| format!("client {} http error, with info: {}", peer, err));
+        },
+        _ = WsServer::wait_shutdown(shutdown) => {},
+    };
+
+    // 服务关闭，处理完当前请求后断开
+    conn.as_mut().graceful_shutdown();
+    match time::timeout(config.shutdown_timeout, conn).await {
+        Ok(result) => {
+            result.map_err(|err| format!("client {} http error, with info: {}", peer, err))
+        }
+        Err(_) => {
+            log::warn!("client {} shutdown timeout, drop in-flight requests", peer);
+            Ok(())
+        }
+    }
+}
+
+async fn handle_request(
+    req: Request<Body>,
+    peer: SocketAddr,
+    route: Arc<Route>,
+    context: Context,
+    config: ClientConfig,
+    shutdown: watch::Receiver<bool>,
+    done: mpsc::Sender<()>,
+) -> Result<Response<Body>, Infallible> {
+    if is_websocket_upgrade(&req) {
+        return Ok(upgrade(req, peer, route, config, shutdown, done));
+    }
+
+    if req.method() != Method::POST {
+        let mut resp = status_response(StatusCode::METHOD_NOT_ALLOWED);
+        resp.headers_mut().insert(ALLOW, "POST".parse().unwrap());
+        return Ok(resp);
+    }
+
+    let body = match read_body(req.into_body(), config.max_message_size).await {
+        Ok(Some(body)) => body,
+        Ok(None) => return Ok(status_response(StatusCode::PAYLOAD_TOO_LARGE)),
+        Err(err) => {
+            log::warn!("client {} read http body error, with info: {}", peer, err);
+            return Ok(status_response(StatusCode::BAD_REQUEST));
+        }
+    };
+
+    // 非UTF-8内容按解析错误返回
+    let resp_str = match String::from_utf8(body) {
+        Ok(req_str) => route_jsonrpc_with_context(route, context, &req_str).await,
+        Err(_) => Some(parse_error_response()),
+    };
+    let resp = match resp_str {
+        Some(resp_str) => Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(resp_str))
+            .unwrap(),
+        // 只有通知时没有响应内容
+        None => status_response(StatusCode::NO_CONTENT),
+    };
+    Ok(resp)
+}
+
+/// 读取请求体，超过max_size时返回None
+async fn read_body(mut body: Body, max_size: usize) -> Result<Option<Vec<u8>>, hyper::Error> {
+    // Content-Length已超出时不再读取
+    if body.size_hint().lower() > max_size as u64 {
+        return Ok(None);
+    }
+
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > max_size {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Some(buf))
+}
+
+fn upgrade(
+    req: Request<Body>,
+    peer: SocketAddr,
+    route: Arc<Route>,
+    config: ClientConfig,
+    shutdown: watch::Receiver<bool>,
+    done: mpsc::Sender<()>,
+) -> Response<Body> {
+    let (parts, body) = req.into_parts();
+    let resp = match create_response(&Request::from_parts(parts, ())) {
+        Ok(resp) => resp,
+        Err(err) => {
+            log::warn!(
+                "client {} websocket upgrade error, with info: {}",
+                peer,
+                err
+            );
+            return status_response(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    // 响应写回后连接交给WebSocket处理
+    tokio::spawn(async move {
+        match body.on_upgrade().await {
+            Ok(upgraded) => {
+                let ws_stream =
+                    WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                if let Err(err) =
+                    WsServer::client_loop(ws_stream, peer, route, config, shutdown).await
+                {
+                    log::warn!("{}", err);
+                }
+            }
+            Err(err) => log::warn!(
+                "client {} websocket upgrade error, with info: {}",
+                peer,
+                err
+            ),
+        }
+        drop(done);
+    });
+
+    resp.map(|_| Body::empty())
+}
+
+fn is_websocket_upgrade(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = status;
+    resp
+}
@@ -0,0 +1,331 @@
+use crate::server::{ClientConfig, WsServer, REQ_QUEUE_LEN};
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
+use jsonrpc_core::route::{parse_error_response, Route};
+use jsonrpc_core::{Context, Session};
+use std::fs::DirBuilder;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, watch};
+use tokio::time;
+
+/// Unix域套接字传输，每条消息为一行JSON(以`\n`分隔)
+///   与 `WsServer` 共用同一个 `Route`，同样支持订阅及服务端请求
+pub struct IpcServer {
+    listener: UnixListener,
+    socket: SocketFile,
+    config: ClientConfig,
+}
+
+// 套接字文件，释放时删除
+struct SocketFile(PathBuf);
+
+impl IpcServer {
+    /// 绑定套接字路径，路径上残留的套接字文件(没有进程监听)会被删除
+    pub fn bind<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        remove_stale_socket(path)?;
+
+        let listener = UnixListener::bind(path)
+            .map_err(|err| format!("bind {} error, with info: {}", path.display(), err))?;
+
+        Ok(Self::with_listener(listener, path))
+    }
+
+    /// 绑定套接字路径并设置套接字文件的权限，如 `0o600` 只允许当前用户连接
+    ///   先在仅当前用户可访问的临时目录中绑定并设置权限，再移动到目标路径，
+    ///   套接字文件出现在目标路径时权限已生效
+    pub fn bind_with_permissions<P: AsRef<Path>>(path: P, mode: u32) -> Result<Self, String> {
+        let path = path.as_ref();
+        remove_stale_socket(path)?;
+
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| format!("{} is not a socket file path", path.display()))?;
+        let mut private_dir = path.as_os_str().to_owned();
+        private_dir.push(format!(".{}.tmp", std::process::id()));
+        let private_dir = PathBuf::from(private_dir);
+
+        DirBuilder::new()
+            .mode(0o700)
+            .create(&private_dir)
+            .map_err(|err| format!("create {} error, with info: {}", private_dir.display(), err))?;
+
+        let private_path = private_dir.join(file_name);
+        let result = UnixListener::bind(&private_path)
+            .map_err(|err| format!("bind {} error, with info: {}", path.display(), err))
+            .and_then(|listener| {
+                std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(mode))
+                    .and_then(|_| std::fs::rename(&private_path, path))
+                    .map(|_| listener)
+                    .map_err(|err| {
+                        format!(
+                            "set {} permissions error, with info: {}",
+                            path.display(),
+                            err
+                        )
+                    })
+            });
+        let _ = std::fs::remove_file(&private_path);
+        let _ = std::fs::remove_dir(&private_dir);
+
+        Ok(Self::with_listener(result?, path))
+    }
+
+    fn with_listener(listener: UnixListener, path: &Path) -> Self {
+        log::info!("Listening on: {}", path.display());
+
+        Self {
+            listener,
+            socket: SocketFile(path.to_path_buf()),
+            config: ClientConfig::default(),
+        }
+    }
+
+    /// 设置单个连接上同时处理的最大请求数，超过时该连接的新请求排队等待
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.config.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// 设置服务端通过 `Session::request` 向客户端发出请求的超时时间
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.config.request_timeout = request_timeout;
+        self
+    }
+
+    /// 设置单行消息的最大字节数，超过时断开该连接
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.config.max_message_size = max_message_size;
+        self
+    }
+
+    /// 设置关闭服务时等待处理中请求完成的最长时间
+    pub fn shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.config.shutdown_timeout = shutdown_timeout;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.socket.0
+    }
+
+    pub async fn listen_loop(self, route: Arc<Route>) {
+        self.listen_with_shutdown(route, future::pending()).await
+    }
+
+    /// 监听直到signal完成，之后停止接受新连接，等待处理中的请求写回响应后断开，
+    ///   所有连接结束后删除套接字文件并返回
+    pub async fn listen_with_shutdown<F: Future<Output = ()>>(self, route: Arc<Route>, signal: F) {
+        let IpcServer {
+            mut listener,
+            socket,
+            config,
+        } = self;
+        let (shutdown_in, shutdown_out) = watch::channel(false);
+        // 每个连接持有一个发送端，全部释放即所有连接已结束
+        let (done_in, mut done_out) = mpsc::channel::<()>(1);
+
+        tokio::pin!(signal);
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        log::warn!("accept error, with info: {}", err);
+                        return;
+                    }
+                },
+                _ = &mut signal => break,
+            };
+
+            let route_ = route.clone();
+            let shutdown = shutdown_out.clone();
+            let done = done_in.clone();
+            tokio::spawn(async move {
+                Self::client_loop(stream, route_, config, shutdown).await;
+                drop(done);
+            });
+        }
+
+        log::info!("server shutdown, wait for clients");
+        drop(listener);
+        let _ = shutdown_in.broadcast(true);
+        drop(done_in);
+        done_out.recv().await;
+        drop(socket);
+        log::info!("server shutdown complete");
+    }
+
+    async fn client_loop(
+        stream: UnixStream,
+        route: Arc<Route>,
+        config: ClientConfig,
+        shutdown: watch::Receiver<bool>,
+    ) {
+        // 对端进程的uid/gid，放入上下文供处理函数鉴权
+        let peer = stream.peer_cred().ok();
+        log::info!("ipc client {:?} connect", peer);
+        let (read_half, mut write_half) = stream.into_split();
+
+        // 与WsServer相同，请求队列不限长度以免阻塞应答的读取
+        let (req_pipe_in, req_pipe_out) = mpsc::unbounded_channel();
+        let (resp_pipe_in, resp_pipe_out) = mpsc::channel(REQ_QUEUE_LEN);
+
+        let (session, push_pipe_out) = Session::with_request_timeout(config.request_timeout);
+
+        let mut context = Context::new().with(session.clone());
+        if let Some(peer) = peer {
+            context = context.with(peer);
+        }
+
+        let dispatching = WsServer::dispatch_loop(
+            route,
+            context,
+            config.max_in_flight,
+            req_pipe_out,
+            resp_pipe_in,
+        );
+        let writing = Self::write_half_loop(&mut write_half, resp_pipe_out, push_pipe_out);
+        tokio::pin!(dispatching);
+        tokio::pin!(writing);
+
+        let shutdown = tokio::select! {
+            _ = &mut dispatching => false,
+            shutdown = Self::read_half_loop(read_half, config.max_message_size, session.clone(), req_pipe_in, shutdown) => shutdown,
+            _ = &mut writing => false,
+        };
+        log::info!("ipc client {:?} close, shutdown: {}", peer, shutdown);
+
+        if shutdown {
+            // 处理中的请求完成并写回响应后写端结束
+            session.close();
+            let drain = future::join(dispatching, writing);
+            if time::timeout(config.shutdown_timeout, drain).await.is_err() {
+                log::warn!(
+                    "ipc client {:?} shutdown timeout, close without waiting for in-flight requests",
+                    peer
+                );
+            }
+        }
+
+        // 连接断开，取消该连接上的所有订阅
+        session.close();
+    }
+
+    /// 按行读取客户端消息直到连接断开(返回false)或服务关闭(返回true)
+    ///   超过max_message_size的行视为连接异常，直接断开
+    async fn read_half_loop(
+        read_half: OwnedReadHalf,
+        max_message_size: usize,
+        session: Session,
+        req_pipe_in: mpsc::UnboundedSender<String>,
+        shutdown: watch::Receiver<bool>,
+    ) -> bool {
+        let mut reader = BufReader::new(read_half);
+        let shutdown = WsServer::wait_shutdown(shutdown);
+        tokio::pin!(shutdown);
+
+        loop {
+            // 最多读取max_message_size加上换行符，避免对端不发送换行时无限读取
+            let mut line = Vec::new();
+            let mut limited = (&mut reader).take(max_message_size as u64 + 1);
+            let ans = tokio::select! {
+                ans = limited.read_until(b'\n', &mut line) => ans,
+                _ = &mut shutdown => return true,
+            };
+
+            match ans {
+                Ok(0) | Err(_) => return false,
+                Ok(_) => {}
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+            } else if line.len() > max_message_size {
+                log::warn!("ipc client message too large, close");
+                return false;
+            }
+
+            // 非UTF-8内容以解析错误响应
+            let msg_str = match String::from_utf8(line) {
+                Ok(msg_str) => msg_str,
+                Err(_) => {
+                    let _ = session.send(parse_error_response());
+                    continue;
+                }
+            };
+
+            // 空行忽略
+            if msg_str.trim().is_empty() {
+                continue;
+            }
+
+            // 客户端对服务端请求的响应直接交给等待者，不进入请求队列
+            if session.handle_response(&msg_str) {
+                continue;
+            }
+
+            if req_pipe_in.send(msg_str).is_err() {
+                return false;
+            }
+        }
+    }
+
+    async fn write_half_loop(
+        write_half: &mut OwnedWriteHalf,
+        resp_pipe_out: mpsc::Receiver<String>,
+        push_pipe_out: impl Stream<Item = String> + Unpin,
+    ) {
+        // 响应与推送通知共用写端
+        let mut outputs = stream::select(resp_pipe_out, push_pipe_out);
+        while let Some(mut msg_str) = outputs.next().await {
+            msg_str.push('\n');
+            if write_half.write_all(msg_str.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// 删除没有进程监听的残留套接字文件，路径被其他文件或正在监听的套接字占用时返回错误
+fn remove_stale_socket(path: &Path) -> Result<(), String> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(format!("stat {} error, with info: {}", path.display(), err)),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(format!("{} exists and is not a socket", path.display()));
+    }
+
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(format!("{} is in use by another server", path.display())),
+        Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+            log::info!("remove stale socket {}", path.display());
+            std::fs::remove_file(path)
+                .map_err(|err| format!("remove {} error, with info: {}", path.display(), err))
+        }
+        Err(err) => Err(format!(
+            "connect {} error, with info: {}",
+            path.display(),
+            err
+        )),
+    }
+}
@@ -1,2 +1,23 @@
 mod server;
 pub use server::WsServer;
+
+mod client;
+pub use client::{
+    Backoff, Batch, ConnectionState, InFlightPolicy, RpcError, Subscription, WsClient,
+    WsClientBuilder,
+};
+
+mod stdio;
+pub use stdio::StdioServer;
+
+#[cfg(unix)]
+mod ipc;
+#[cfg(unix)]
+pub use ipc::IpcServer;
+
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "tls")]
+pub use tokio_rustls::rustls;
@@ -1,20 +1,73 @@
-use futures_util::stream::{SplitSink, SplitStream};
-use futures_util::{SinkExt, StreamExt};
-use jsonrpc_core::route::{route_jsonrpc, Route};
+use futures_util::future;
+use futures_util::stream::{self, SplitSink, SplitStream};
+use futures_util::{SinkExt, Stream, StreamExt};
+use jsonrpc_core::route::{route_jsonrpc_with_context, Route};
+use jsonrpc_core::{Context, Session};
+use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch, Semaphore};
+use tokio::time;
 use tokio_tungstenite::accept_async;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::WebSocketStream;
 
-type WebSockWriteHalf = SplitSink<WebSocketStream<TcpStream>, Message>;
-type WebSockReadHalf = SplitStream<WebSocketStream<TcpStream>>;
+#[cfg(feature = "http")]
+use crate::http;
+#[cfg(feature = "tls")]
+use crate::tls;
+#[cfg(feature = "tls")]
+use tokio_rustls::rustls::ServerConfig;
+#[cfg(feature = "tls")]
+use tokio_rustls::TlsAcceptor;
 
-const REQ_QUEUE_LEN: usize = 10;
+type WebSockWriteHalf<S> = SplitSink<WebSocketStream<S>, Message>;
+type WebSockReadHalf<S> = SplitStream<WebSocketStream<S>>;
+
+pub(crate) const REQ_QUEUE_LEN: usize = 10;
+
+const MAX_IN_FLIGHT: usize = 32;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+const MAX_MESSAGE_SIZE: usize = 16 << 20;
 
 pub struct WsServer {
     listener: TcpListener,
+    config: ClientConfig,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
+}
+
+// 每个连接共用的配置
+#[derive(Clone, Copy)]
+pub(crate) struct ClientConfig {
+    pub(crate) max_in_flight: usize,
+    pub(crate) request_timeout: Duration,
+    pub(crate) shutdown_timeout: Duration,
+    pub(crate) max_message_size: usize,
+    #[cfg(feature = "http")]
+    http: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: MAX_IN_FLIGHT,
+            request_timeout: REQUEST_TIMEOUT,
+            shutdown_timeout: SHUTDOWN_TIMEOUT,
+            max_message_size: MAX_MESSAGE_SIZE,
+            #[cfg(feature = "http")]
+            http: false,
+        }
+    }
 }
 
 impl WsServer {
@@ -25,91 +78,382 @@ impl WsServer {
 
         log::info!("Listening on: {}", &bind_transport);
 
-        let instance = Self { listener };
+        let instance = Self {
+            listener,
+            config: ClientConfig::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        };
 
         Ok(instance)
     }
 
-    pub async fn listen_loop(mut self, route: Arc<Route>) {
-        while let Ok((stream, _)) = self.listener.accept().await {
+    /// 设置单个连接上同时处理的最大请求数，超过时该连接的新请求排队等待
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.config.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// 设置服务端通过 `Session::request` 向客户端发出请求的超时时间
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.config.request_timeout = request_timeout;
+        self
+    }
+
+    /// 设置关闭服务时等待处理中请求完成的最长时间，超时后直接断开连接
+    pub fn shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.config.shutdown_timeout = shutdown_timeout;
+        self
+    }
+
+    /// 同一端口同时接受HTTP POST请求，WebSocket连接通过HTTP Upgrade建立
+    #[cfg(feature = "http")]
+    pub fn http(mut self) -> Self {
+        self.config.http = true;
+        self
+    }
+
+    /// 设置HTTP POST请求体的最大字节数，超过时返回413
+    #[cfg(feature = "http")]
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.config.max_message_size = max_body_size;
+        self
+    }
+
+    /// 启用TLS(wss)，在WebSocket握手之前完成TLS握手
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: Arc<ServerConfig>) -> Self {
+        self.tls = Some(TlsAcceptor::from(config));
+        self
+    }
+
+    /// 使用PEM格式的证书链及私钥(PKCS#8或RSA)启用TLS
+    #[cfg(feature = "tls")]
+    pub fn tls_pem(self, cert_pem: &[u8], key_pem: &[u8]) -> Result<Self, String> {
+        let config = tls::server_config(cert_pem, key_pem)?;
+        Ok(self.tls(Arc::new(config)))
+    }
+
+    /// 从PEM文件读取证书链及私钥启用TLS
+    #[cfg(feature = "tls")]
+    pub fn tls_pem_files<P: AsRef<std::path::Path>>(
+        self,
+        cert_path: P,
+        key_path: P,
+    ) -> Result<Self, String> {
+        let read = |path: &std::path::Path| {
+            std::fs::read(path)
+                .map_err(|err| format!("read {} error, with info: {}", path.display(), err))
+        };
+        let cert_pem = read(cert_path.as_ref())?;
+        let key_pem = read(key_path.as_ref())?;
+        self.tls_pem(&cert_pem, &key_pem)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.listener.local_addr().map_err(|err| err.to_string())
+    }
+
+    pub async fn listen_loop(self, route: Arc<Route>) {
+        self.listen_with_shutdown(route, future::pending()).await
+    }
+
+    /// 监听直到signal完成，之后停止接受新连接，
+    ///   每个连接停止读取新请求，等待处理中的请求写回响应后发送Close(1001)断开，
+    ///   所有连接结束后返回
+    pub async fn listen_with_shutdown<F: Future<Output = ()>>(
+        mut self,
+        route: Arc<Route>,
+        signal: F,
+    ) {
+        let (shutdown_in, shutdown_out) = watch::channel(false);
+        // 每个连接持有一个发送端，全部释放即所有连接已结束
+        let (done_in, mut done_out) = mpsc::channel::<()>(1);
+
+        tokio::pin!(signal);
+        loop {
+            let stream = tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        log::warn!("accept error, with info: {}", err);
+                        return;
+                    }
+                },
+                _ = &mut signal => break,
+            };
+
             let route_ = route.clone();
+            let config = self.config;
+            let shutdown = shutdown_out.clone();
+            let done = done_in.clone();
+            #[cfg(feature = "tls")]
+            let tls = self.tls.clone();
             tokio::spawn(async move {
-                if let Err(err) = Self::client_loop(stream, route_).await {
+                let result = Self::serve(
+                    stream,
+                    route_,
+                    config,
+                    shutdown,
+                    done,
+                    #[cfg(feature = "tls")]
+                    tls,
+                )
+                .await;
+                if let Err(err) = result {
                     log::warn!("{}", err);
                 }
             });
         }
+
+        log::info!("server shutdown, wait for clients");
+        drop(self);
+        let _ = shutdown_in.broadcast(true);
+        drop(done_in);
+        done_out.recv().await;
+        log::info!("server shutdown complete");
     }
 
-    async fn client_loop(stream: TcpStream, route: Arc<Route>) -> Result<(), String> {
+    async fn serve(
+        stream: TcpStream,
+        route: Arc<Route>,
+        config: ClientConfig,
+        shutdown: watch::Receiver<bool>,
+        done: mpsc::Sender<()>,
+        #[cfg(feature = "tls")] tls: Option<TlsAcceptor>,
+    ) -> Result<(), String> {
         let peer = stream
             .peer_addr()
             .map_err(|err| format!("get client peer_addr error, with info: {}", err))?;
 
-        let ws_stream = accept_async(stream)
-            .await
-            .map_err(|err| format!("ws_stream accept error, with info: {}", err))?;
+        #[cfg(feature = "tls")]
+        {
+            if let Some(acceptor) = tls {
+                let stream = match Self::until_shutdown(acceptor.accept(stream), &shutdown).await {
+                    Some(stream) => stream.map_err(|err| {
+                        format!("client {} tls accept error, with info: {}", peer, err)
+                    })?,
+                    None => return Ok(()),
+                };
+                return Self::serve_stream(stream, peer, route, config, shutdown, done).await;
+            }
+        }
+
+        Self::serve_stream(stream, peer, route, config, shutdown, done).await
+    }
+
+    async fn serve_stream<S>(
+        stream: S,
+        peer: SocketAddr,
+        route: Arc<Route>,
+        config: ClientConfig,
+        shutdown: watch::Receiver<bool>,
+        done: mpsc::Sender<()>,
+    ) -> Result<(), String>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        #[cfg(feature = "http")]
+        {
+            if config.http {
+                return http::serve_connection(stream, peer, route, config, shutdown, done).await;
+            }
+        }
+
+        let ws_stream = match Self::until_shutdown(accept_async(stream), &shutdown).await {
+            Some(ws_stream) => {
+                ws_stream.map_err(|err| format!("ws_stream accept error, with info: {}", err))?
+            }
+            None => return Ok(()),
+        };
+
+        let result = Self::client_loop(ws_stream, peer, route, config, shutdown).await;
+        // 连接结束后才释放，关闭服务时据此等待所有连接结束
+        drop(done);
+        result
+    }
 
+    /// 等待握手完成，期间服务关闭时返回None，避免不发送数据的客户端阻塞关闭
+    async fn until_shutdown<F: Future>(
+        handshake: F,
+        shutdown: &watch::Receiver<bool>,
+    ) -> Option<F::Output> {
+        tokio::select! {
+            output = handshake => Some(output),
+            _ = Self::wait_shutdown(shutdown.clone()) => None,
+        }
+    }
+
+    pub(crate) async fn client_loop<S>(
+        ws_stream: WebSocketStream<S>,
+        peer: SocketAddr,
+        route: Arc<Route>,
+        config: ClientConfig,
+        shutdown: watch::Receiver<bool>,
+    ) -> Result<(), String>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         log::info!("client {} connect", peer);
-        let (write_half, read_half) = ws_stream.split();
+        let (mut write_half, read_half) = ws_stream.split();
 
-        let (req_pipe_in, req_pipe_out) = mpsc::channel(REQ_QUEUE_LEN);
+        // 请求队列不限长度，读取不会因并发数已满而停止，对端对服务端请求的应答总能及时处理
+        let (req_pipe_in, req_pipe_out) = mpsc::unbounded_channel();
         let (resp_pipe_in, resp_pipe_out) = mpsc::channel(REQ_QUEUE_LEN);
 
-        tokio::select! {
-            _ = Self::dispatch_loop(route, req_pipe_out, resp_pipe_in) => {
-                log::info!("client {} close because dispatch_loop", peer);
-            },
-            _ = Self::read_half_loop(read_half, req_pipe_in) => {
-                log::info!("client {} close because read_half", peer);
-            },
-            _ = Self::write_half_loop(write_half, resp_pipe_out) => {
-                log::info!("client {} close because write_half", peer);
-            },
+        // 会话用于向客户端推送订阅通知及发出请求
+        let (session, push_pipe_out) = Session::with_request_timeout(config.request_timeout);
+
+        // 连接上下文，处理函数可以取得对端地址及会话
+        let context = Context::new().with(peer).with(session.clone());
+
+        let shutdown = {
+            let dispatching = Self::dispatch_loop(
+                route,
+                context,
+                config.max_in_flight,
+                req_pipe_out,
+                resp_pipe_in,
+            );
+            let writing = Self::write_half_loop(&mut write_half, resp_pipe_out, push_pipe_out);
+            tokio::pin!(dispatching);
+            tokio::pin!(writing);
+
+            let shutdown = tokio::select! {
+                _ = &mut dispatching => {
+                    log::info!("client {} close because dispatch_loop", peer);
+                    false
+                },
+                shutdown = Self::read_half_loop(read_half, session.clone(), req_pipe_in, shutdown) => {
+                    log::info!("client {} close because read_half, shutdown: {}", peer, shutdown);
+                    shutdown
+                },
+                _ = &mut writing => {
+                    log::info!("client {} close because write_half", peer);
+                    false
+                },
+            };
+
+            if shutdown {
+                // 取消订阅及等待客户端应答的请求，处理中的请求完成并写回响应后写端结束
+                session.close();
+                let drain = future::join(dispatching, writing);
+                if time::timeout(config.shutdown_timeout, drain).await.is_err() {
+                    log::warn!(
+                        "client {} shutdown timeout, close without waiting for in-flight requests",
+                        peer
+                    );
+                }
+            }
+            shutdown
         };
 
+        // 连接断开，取消该连接上的所有订阅
+        session.close();
+
+        if shutdown {
+            let frame = CloseFrame {
+                code: CloseCode::Away,
+                reason: "server shutdown".into(),
+            };
+            let _ = write_half.send(Message::Close(Some(frame))).await;
+        }
+
         Ok(())
     }
 
-    async fn dispatch_loop(
+    pub(crate) async fn dispatch_loop(
         route: Arc<Route>,
-        mut req_pipe: mpsc::Receiver<String>,
-        mut resp_pipe: mpsc::Sender<String>,
+        context: Context,
+        max_in_flight: usize,
+        mut req_pipe: mpsc::UnboundedReceiver<String>,
+        resp_pipe: mpsc::Sender<String>,
     ) {
+        let in_flight = Arc::new(Semaphore::new(max_in_flight));
+
         while let Some(req_str) = req_pipe.recv().await {
+            // 达到最大并发数时等待已有请求完成
+            let permit = in_flight.clone().acquire_owned().await;
+
             let route_ = route.clone();
-            let resp_str = route_jsonrpc(route_, &req_str).await;
-            if let Err(_) = resp_pipe.send(resp_str).await {
-                // 处理完客户端已断开，忽略
-                return;
-            }
+            let context = context.clone();
+            let mut resp_pipe = resp_pipe.clone();
+
+            // 每个请求单独执行，响应按完成顺序写回
+            tokio::spawn(async move {
+                // 通知类请求没有响应
+                if let Some(resp_str) = route_jsonrpc_with_context(route_, context, &req_str).await
+                {
+                    // 处理完客户端已断开，忽略
+                    let _ = resp_pipe.send(resp_str).await;
+                }
+                drop(permit);
+            });
+        }
+
+        // 请求队列关闭后，取得全部许可即所有请求已处理完成
+        let mut permits = Vec::with_capacity(max_in_flight);
+        for _ in 0..max_in_flight {
+            permits.push(in_flight.acquire().await);
         }
     }
 
-    async fn read_half_loop(mut read_half: WebSockReadHalf, mut req_pipe_in: mpsc::Sender<String>) {
-        while let Some(ans) = read_half.next().await {
+    /// 读取客户端消息直到连接断开(返回false)或服务关闭(返回true)
+    async fn read_half_loop<S: AsyncRead + AsyncWrite + Unpin>(
+        mut read_half: WebSockReadHalf<S>,
+        session: Session,
+        req_pipe_in: mpsc::UnboundedSender<String>,
+        shutdown: watch::Receiver<bool>,
+    ) -> bool {
+        let shutdown = Self::wait_shutdown(shutdown);
+        tokio::pin!(shutdown);
+
+        loop {
+            let ans = tokio::select! {
+                ans = read_half.next() => ans,
+                _ = &mut shutdown => return true,
+            };
+
             match ans {
-                Err(_) => {
-                    return;
+                None | Some(Err(_)) => {
+                    return false;
                 }
-                Ok(Message::Text(msg_str)) => {
-                    if let Err(_) = req_pipe_in.send(msg_str).await {
-                        return;
+                Some(Ok(Message::Text(msg_str))) => {
+                    // 客户端对服务端请求的响应直接交给等待者，不进入请求队列
+                    if session.handle_response(&msg_str) {
+                        continue;
+                    }
+
+                    if req_pipe_in.send(msg_str).is_err() {
+                        return false;
                     }
                 }
-                Ok(Message::Ping(_)) => log::debug!("recv message ping/pong"),
-                Ok(Message::Pong(_)) => log::debug!("recv message ping/pong"),
-                Ok(_) => log::debug!("data format not String, ignore this item"),
+                Some(Ok(Message::Ping(_))) => log::debug!("recv message ping/pong"),
+                Some(Ok(Message::Pong(_))) => log::debug!("recv message ping/pong"),
+                Some(Ok(_)) => log::debug!("data format not String, ignore this item"),
+            }
+        }
+    }
+
+    pub(crate) async fn wait_shutdown(mut shutdown: watch::Receiver<bool>) {
+        while let Some(shutdown) = shutdown.recv().await {
+            if shutdown {
+                return;
             }
         }
+        // 监听已异常退出，连接继续服务
+        future::pending().await
     }
 
-    async fn write_half_loop(
-        mut write_half: WebSockWriteHalf,
-        mut resp_pipe_out: mpsc::Receiver<String>,
+    async fn write_half_loop<S: AsyncRead + AsyncWrite + Unpin>(
+        write_half: &mut WebSockWriteHalf<S>,
+        resp_pipe_out: mpsc::Receiver<String>,
+        push_pipe_out: impl Stream<Item = String> + Unpin,
     ) {
-        while let Some(msg_str) = resp_pipe_out.recv().await {
-            if let Err(_) = write_half.send(Message::Text(msg_str)).await {
+        // 响应与推送通知共用写端
+        let mut outputs = stream::select(resp_pipe_out, push_pipe_out);
+        while let Some(msg_str) = outputs.next().await {
+            if write_half.send(Message::Text(msg_str)).await.is_err() {
                 return;
             }
         }
@@ -0,0 +1,220 @@
+use crate::server::{ClientConfig, WsServer, REQ_QUEUE_LEN};
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
+use jsonrpc_core::route::{parse_error_response, Route};
+use jsonrpc_core::{Context, Session};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{
+    self, AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+    BufReader, Stdin, Stdout,
+};
+use tokio::sync::mpsc;
+
+const MAX_HEADER_LINE: usize = 4096;
+
+/// 标准输入输出传输，消息使用与LSP相同的 `Content-Length` 头分帧：
+///   `Content-Length: <字节数>\r\n\r\n<JSON>`
+///   输入结束(EOF)后等待处理中的请求写回响应再返回
+pub struct StdioServer<R, W> {
+    reader: R,
+    writer: W,
+    config: ClientConfig,
+}
+
+impl StdioServer<Stdin, Stdout> {
+    pub fn new() -> Self {
+        Self::with_io(io::stdin(), io::stdout())
+    }
+}
+
+impl Default for StdioServer<Stdin, Stdout> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, W> StdioServer<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    /// 使用指定的输入输出，如子进程的管道
+    pub fn with_io(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            config: ClientConfig::default(),
+        }
+    }
+
+    /// 设置同时处理的最大请求数，超过时新请求排队等待
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.config.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// 设置服务端通过 `Session::request` 向对端发出请求的超时时间
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.config.request_timeout = request_timeout;
+        self
+    }
+
+    /// 设置单帧内容的最大字节数，`Content-Length` 超过时返回Err
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.config.max_message_size = max_message_size;
+        self
+    }
+
+    /// 服务直到输入结束，分帧错误时返回Err
+    pub async fn serve(self, route: Arc<Route>) -> Result<(), String> {
+        let StdioServer {
+            reader,
+            mut writer,
+            config,
+        } = self;
+
+        // 与WsServer相同，请求队列不限长度以免阻塞应答的读取
+        let (req_pipe_in, req_pipe_out) = mpsc::unbounded_channel();
+        let (resp_pipe_in, resp_pipe_out) = mpsc::channel(REQ_QUEUE_LEN);
+
+        // 会话用于向对端推送通知及发出请求
+        let (session, push_pipe_out) = Session::with_request_timeout(config.request_timeout);
+        let context = Context::new().with(session.clone());
+
+        let dispatching = WsServer::dispatch_loop(
+            route,
+            context,
+            config.max_in_flight,
+            req_pipe_out,
+            resp_pipe_in,
+        );
+        let writing = Self::write_loop(&mut writer, resp_pipe_out, push_pipe_out);
+        tokio::pin!(dispatching);
+        tokio::pin!(writing);
+
+        // 读取结束前请求队列不会关闭，dispatching不会先结束
+        let result = tokio::select! {
+            result = Self::read_loop(reader, config.max_message_size, session.clone(), req_pipe_in) => result,
+            _ = &mut dispatching => Ok(()),
+            _ = &mut writing => {
+                session.close();
+                return Err("stdio write error".to_string());
+            },
+        };
+
+        // 输入已结束，对端不会再应答服务端请求；处理中的请求完成并写回响应后写端结束
+        session.close();
+        future::join(dispatching, writing).await;
+
+        result
+    }
+
+    async fn read_loop(
+        reader: R,
+        max_message_size: usize,
+        session: Session,
+        req_pipe_in: mpsc::UnboundedSender<String>,
+    ) -> Result<(), String> {
+        let mut reader = BufReader::new(reader);
+
+        while let Some(body) = read_frame(&mut reader, max_message_size).await? {
+            // 非UTF-8内容以解析错误响应
+            let msg_str = match String::from_utf8(body) {
+                Ok(msg_str) => msg_str,
+                Err(_) => {
+                    let _ = session.send(parse_error_response());
+                    continue;
+                }
+            };
+
+            // 对端对服务端请求的响应直接交给等待者
+            if session.handle_response(&msg_str) {
+                continue;
+            }
+
+            if req_pipe_in.send(msg_str).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_loop(
+        writer: &mut W,
+        resp_pipe_out: mpsc::Receiver<String>,
+        push_pipe_out: impl Stream<Item = String> + Unpin,
+    ) {
+        // 响应与推送通知共用输出
+        let mut outputs = stream::select(resp_pipe_out, push_pipe_out);
+        while let Some(msg_str) = outputs.next().await {
+            let frame = format!("Content-Length: {}\r\n\r\n{}", msg_str.len(), msg_str);
+            if writer.write_all(frame.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// 读取一帧，输入在帧之间结束时返回None
+async fn read_frame<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    max_message_size: usize,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut content_length = None;
+    let mut has_header = false;
+
+    loop {
+        // 头部行限制长度，避免对端不发送换行时无限读取
+        let mut line = String::new();
+        let n = (&mut *reader)
+            .take(MAX_HEADER_LINE as u64)
+            .read_line(&mut line)
+            .await
+            .map_err(|err| format!("stdio read error, with info: {}", err))?;
+        if n == MAX_HEADER_LINE && !line.ends_with('\n') {
+            return Err("stdio frame header line too long".to_string());
+        }
+        if n == 0 {
+            if has_header {
+                return Err("stdio unexpected eof in frame header".to_string());
+            }
+            return Ok(None);
+        }
+
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        if line.is_empty() {
+            // 头部之前的空行忽略
+            if has_header {
+                break;
+            }
+            continue;
+        }
+        has_header = true;
+
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("stdio frame header format error: {}", line))?;
+        let (name, value) = (name.trim(), value.trim());
+
+        // 其他头部(如Content-Type)忽略
+        if name.eq_ignore_ascii_case("content-length") {
+            let len = value
+                .parse::<usize>()
+                .map_err(|_| format!("stdio frame content-length error: {}", value))?;
+            content_length = Some(len);
+        }
+    }
+
+    let len = content_length.ok_or_else(|| "stdio frame without content-length".to_string())?;
+    if len > max_message_size {
+        return Err(format!("stdio frame too large: {}", len));
+    }
+    let mut body = vec![0u8; len];
+    reader
+        .read_exact(&mut body)
+        .await
+        .map_err(|err| format!("stdio read error, with info: {}", err))?;
+
+    Ok(Some(body))
+}
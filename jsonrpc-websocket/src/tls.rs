@@ -0,0 +1,30 @@
+use std::io::BufReader;
+use tokio_rustls::rustls::internal::pemfile;
+use tokio_rustls::rustls::{NoClientAuth, ServerConfig};
+
+/// 由PEM格式的证书链及私钥创建TLS服务端配置
+pub(crate) fn server_config(cert_pem: &[u8], key_pem: &[u8]) -> Result<ServerConfig, String> {
+    let certs = pemfile::certs(&mut BufReader::new(cert_pem))
+        .map_err(|_| "tls certificate pem format error".to_string())?;
+    if certs.is_empty() {
+        return Err("tls certificate not found in pem".to_string());
+    }
+
+    // 优先PKCS#8，其次RSA私钥
+    let mut keys = pemfile::pkcs8_private_keys(&mut BufReader::new(key_pem))
+        .map_err(|_| "tls private key pem format error".to_string())?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut BufReader::new(key_pem))
+            .map_err(|_| "tls private key pem format error".to_string())?;
+    }
+    let key = keys
+        .into_iter()
+        .next()
+        .ok_or_else(|| "tls private key not found in pem".to_string())?;
+
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config
+        .set_single_cert(certs, key)
+        .map_err(|err| format!("tls certificate error, with info: {}", err))?;
+    Ok(config)
+}
@@ -0,0 +1,1304 @@
+use futures_util::{SinkExt, StreamExt};
+use jsonrpc_core::route::Route;
+use jsonrpc_core::{Data, Params, Session, Sink};
+use jsonrpc_lite::Error as JsonRpcError;
+use jsonrpc_websocket::{Backoff, ConnectionState, InFlightPolicy, RpcError, WsClient, WsServer};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio::time::{self, Duration};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, WebSocketStream};
+
+#[derive(Debug, Serialize)]
+pub enum TestError {}
+
+impl From<TestError> for JsonRpcError {
+    fn from(_: TestError) -> JsonRpcError {
+        JsonRpcError::internal_error()
+    }
+}
+
+async fn route_sleep(Params(timeout_ms): Params<u64>) -> Result<u64, TestError> {
+    time::delay_for(Duration::from_millis(timeout_ms)).await;
+    Ok(timeout_ms)
+}
+
+fn test_route() -> Arc<Route> {
+    Arc::new(
+        Route::new()
+            .to("route_sleep".to_string(), route_sleep)
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn start_server(server: WsServer, route: Arc<Route>) -> SocketAddr {
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.listen_loop(route));
+    addr
+}
+
+async fn connect(addr: SocketAddr) -> WebSocketStream<TcpStream> {
+    let (ws_stream, _) = connect_async(url::Url::parse(&format!("ws://{}", addr)).unwrap())
+        .await
+        .unwrap();
+    ws_stream
+}
+
+async fn send(ws_stream: &mut WebSocketStream<TcpStream>, req: Value) {
+    ws_stream
+        .send(Message::Text(req.to_string()))
+        .await
+        .unwrap();
+}
+
+async fn recv(ws_stream: &mut WebSocketStream<TcpStream>) -> Value {
+    loop {
+        match ws_stream.next().await.unwrap().unwrap() {
+            Message::Text(msg) => return serde_json::from_str(&msg).unwrap(),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_ws_pipelining() {
+    let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+    let addr = start_server(server, test_route()).await;
+    let mut ws_stream = connect(addr).await;
+
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [1000], "id": "slow"}),
+    )
+    .await;
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [10], "id": "fast"}),
+    )
+    .await;
+
+    // 快请求先于慢请求返回
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": 10, "id": "fast"}),
+        recv(&mut ws_stream).await
+    );
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": 1000, "id": "slow"}),
+        recv(&mut ws_stream).await
+    );
+}
+
+#[tokio::test]
+async fn test_ws_max_in_flight() {
+    let server = WsServer::bind("127.0.0.1:0".to_string())
+        .await
+        .unwrap()
+        .max_in_flight(1);
+    let addr = start_server(server, test_route()).await;
+    let mut ws_stream = connect(addr).await;
+
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [500], "id": "slow"}),
+    )
+    .await;
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [10], "id": "fast"}),
+    )
+    .await;
+
+    // 同时只处理一个请求，按顺序返回
+    assert_eq!("slow", recv(&mut ws_stream).await["id"]);
+    assert_eq!("fast", recv(&mut ws_stream).await["id"]);
+}
+
+async fn route_ticks(stopped: Data<Arc<AtomicBool>>, sink: Sink) -> Result<(), TestError> {
+    tokio::spawn(async move {
+        let mut n = 0u64;
+        while sink.notify(n).is_ok() {
+            n += 1;
+            time::delay_for(Duration::from_millis(10)).await;
+        }
+        stopped.get_ref().store(true, Ordering::SeqCst);
+    });
+    Ok(())
+}
+
+fn subscription_route(stopped: Arc<AtomicBool>) -> Arc<Route> {
+    Arc::new(
+        Route::new()
+            .data(stopped)
+            .subscription(
+                "subscribe_ticks".to_string(),
+                "ticks".to_string(),
+                "unsubscribe_ticks".to_string(),
+                route_ticks,
+            )
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn wait_stopped(stopped: &AtomicBool) {
+    for _ in 0..100 {
+        if stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        time::delay_for(Duration::from_millis(10)).await;
+    }
+    panic!("producer not stopped");
+}
+
+#[tokio::test]
+async fn test_ws_subscription() {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+    let addr = start_server(server, subscription_route(stopped.clone())).await;
+    let mut ws_stream = connect(addr).await;
+
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "subscribe_ticks", "id": 1}),
+    )
+    .await;
+
+    // 订阅响应与第一条推送的先后顺序不确定
+    let mut sub_id = Value::Null;
+    let mut ticks = Vec::new();
+    while sub_id.is_null() || ticks.len() < 3 {
+        let msg = recv(&mut ws_stream).await;
+        if msg["id"] == 1 {
+            sub_id = msg["result"].clone();
+        } else {
+            assert_eq!("ticks", msg["method"]);
+            ticks.push(msg["params"].clone());
+        }
+    }
+    assert_eq!(sub_id, ticks[0]["subscription"]);
+    assert_eq!(
+        json!([0, 1, 2]),
+        json!([ticks[0]["result"], ticks[1]["result"], ticks[2]["result"]])
+    );
+
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "unsubscribe_ticks", "params": [sub_id], "id": 2}),
+    )
+    .await;
+    loop {
+        let msg = recv(&mut ws_stream).await;
+        if msg["id"] == 2 {
+            assert_eq!(true, msg["result"]);
+            break;
+        }
+    }
+    wait_stopped(&stopped).await;
+}
+
+#[tokio::test]
+async fn test_ws_subscription_disconnect() {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+    let addr = start_server(server, subscription_route(stopped.clone())).await;
+    let mut ws_stream = connect(addr).await;
+
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "subscribe_ticks", "id": 1}),
+    )
+    .await;
+    recv(&mut ws_stream).await;
+
+    // 客户端断开后订阅自动取消
+    drop(ws_stream);
+    wait_stopped(&stopped).await;
+}
+
+async fn route_ask(
+    session: Session,
+    Params(question): Params<String>,
+) -> Result<String, TestError> {
+    let answer: String = session
+        .request("ask", vec![question])
+        .await
+        .map_err(|err| err.to_string())
+        .unwrap_or_else(|err| err);
+    Ok(answer)
+}
+
+#[tokio::test]
+async fn test_ws_server_request() {
+    let route = Arc::new(
+        Route::new()
+            .to("route_ask".to_string(), route_ask)
+            .build()
+            .unwrap(),
+    );
+    let server = WsServer::bind("127.0.0.1:0".to_string())
+        .await
+        .unwrap()
+        .max_in_flight(1)
+        .request_timeout(Duration::from_millis(200));
+    let addr = start_server(server, route).await;
+    let mut ws_stream = connect(addr).await;
+
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_ask", "params": ["name?"], "id": 1}),
+    )
+    .await;
+
+    // 占用唯一并发数的请求等待客户端应答，应答不经过请求队列
+    let req = recv(&mut ws_stream).await;
+    assert_eq!("ask", req["method"]);
+    assert_eq!(json!(["name?"]), req["params"]);
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "result": "ws", "id": req["id"]}),
+    )
+    .await;
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": "ws", "id": 1}),
+        recv(&mut ws_stream).await
+    );
+
+    // 客户端不应答时超时
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_ask", "params": ["again?"], "id": 2}),
+    )
+    .await;
+    assert_eq!("ask", recv(&mut ws_stream).await["method"]);
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": "peer request timeout", "id": 2}),
+        recv(&mut ws_stream).await
+    );
+}
+
+async fn recv_close(ws_stream: &mut WebSocketStream<TcpStream>) -> CloseCode {
+    loop {
+        match ws_stream.next().await.unwrap().unwrap() {
+            Message::Close(frame) => return frame.unwrap().code,
+            Message::Text(msg) => panic!("unexpected message {}", msg),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_ws_server_request_pipelined() {
+    let route = Arc::new(
+        Route::new()
+            .to("route_ask".to_string(), route_ask)
+            .to("route_sleep".to_string(), route_sleep)
+            .build()
+            .unwrap(),
+    );
+    let server = WsServer::bind("127.0.0.1:0".to_string())
+        .await
+        .unwrap()
+        .max_in_flight(1)
+        .request_timeout(Duration::from_secs(1));
+    let addr = start_server(server, route).await;
+    let mut ws_stream = connect(addr).await;
+
+    // 唯一的并发数被等待应答的请求占用，之后排队的请求多于请求队列长度
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_ask", "params": ["name?"], "id": "ask"}),
+    )
+    .await;
+    for id in 0..20 {
+        send(
+            &mut ws_stream,
+            json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [1], "id": id}),
+        )
+        .await;
+    }
+
+    // 应答不被排队的请求阻塞
+    let req = recv(&mut ws_stream).await;
+    assert_eq!("ask", req["method"]);
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "result": "ws", "id": req["id"]}),
+    )
+    .await;
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": "ws", "id": "ask"}),
+        recv(&mut ws_stream).await
+    );
+    for id in 0..20 {
+        assert_eq!(id, recv(&mut ws_stream).await["id"]);
+    }
+}
+
+#[tokio::test]
+async fn test_ws_shutdown() {
+    let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    let (signal_in, signal_out) = futures_channel::oneshot::channel::<()>();
+    let listening = tokio::spawn(server.listen_with_shutdown(test_route(), async {
+        let _ = signal_out.await;
+    }));
+    let mut ws_stream = connect(addr).await;
+
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [300], "id": 1}),
+    )
+    .await;
+    time::delay_for(Duration::from_millis(50)).await;
+    signal_in.send(()).unwrap();
+
+    // 处理中的请求完成后才关闭连接
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": 300, "id": 1}),
+        recv(&mut ws_stream).await
+    );
+    assert_eq!(CloseCode::Away, recv_close(&mut ws_stream).await);
+    time::timeout(Duration::from_secs(1), listening)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // 不再接受新连接
+    assert!(
+        connect_async(url::Url::parse(&format!("ws://{}", addr)).unwrap())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_ws_shutdown_timeout() {
+    let server = WsServer::bind("127.0.0.1:0".to_string())
+        .await
+        .unwrap()
+        .shutdown_timeout(Duration::from_millis(100));
+    let addr = server.local_addr().unwrap();
+    let (signal_in, signal_out) = futures_channel::oneshot::channel::<()>();
+    let listening = tokio::spawn(server.listen_with_shutdown(test_route(), async {
+        let _ = signal_out.await;
+    }));
+    let mut ws_stream = connect(addr).await;
+
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [5000], "id": 1}),
+    )
+    .await;
+    time::delay_for(Duration::from_millis(50)).await;
+    signal_in.send(()).unwrap();
+
+    // 超时后不等待处理中的请求
+    assert_eq!(CloseCode::Away, recv_close(&mut ws_stream).await);
+    time::timeout(Duration::from_secs(1), listening)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_ws_shutdown_idle_handshake() {
+    let server = WsServer::bind("127.0.0.1:0".to_string())
+        .await
+        .unwrap()
+        .shutdown_timeout(Duration::from_millis(100));
+    let addr = server.local_addr().unwrap();
+    let (signal_in, signal_out) = futures_channel::oneshot::channel::<()>();
+    let listening = tokio::spawn(server.listen_with_shutdown(test_route(), async {
+        let _ = signal_out.await;
+    }));
+
+    // 建立TCP连接后不发送握手
+    let _stream = TcpStream::connect(addr).await.unwrap();
+    time::delay_for(Duration::from_millis(50)).await;
+    signal_in.send(()).unwrap();
+
+    time::timeout(Duration::from_secs(1), listening)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_ws_client() {
+    let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+    let addr = start_server(server, test_route()).await;
+    let client = WsClient::connect(&format!("ws://{}", addr)).await.unwrap();
+
+    // 并发调用按id对应各自的响应
+    let (slow, fast) = futures_util::future::join(
+        client.call::<_, u64>("route_sleep", [300]),
+        client.call::<_, u64>("route_sleep", [10]),
+    )
+    .await;
+    assert_eq!(Ok(300), slow);
+    assert_eq!(Ok(10), fast);
+
+    match client.call::<_, u64>("route_missing", ()).await {
+        Err(RpcError::Rpc(err)) => assert_eq!(-32601, err.code),
+        _ => panic!("expect method not found"),
+    }
+    match client.call::<_, u64>("route_sleep", ["x"]).await {
+        Err(RpcError::Rpc(err)) => assert_eq!(-32602, err.code),
+        _ => panic!("expect invalid params"),
+    }
+    match client.call::<_, String>("route_sleep", [1]).await {
+        Err(RpcError::Parse(_)) => {}
+        _ => panic!("expect parse error"),
+    }
+
+    client.notify("route_sleep", [1]).unwrap();
+
+    client.close();
+    assert!(client.is_closed());
+    assert_eq!(
+        Err(RpcError::Closed),
+        client.call::<_, u64>("route_sleep", [1]).await
+    );
+}
+
+#[tokio::test]
+async fn test_ws_client_timeout_and_disconnect() {
+    let server = WsServer::bind("127.0.0.1:0".to_string())
+        .await
+        .unwrap()
+        .shutdown_timeout(Duration::from_millis(10));
+    let addr = server.local_addr().unwrap();
+    let (signal_in, signal_out) = futures_channel::oneshot::channel::<()>();
+    tokio::spawn(server.listen_with_shutdown(test_route(), async {
+        let _ = signal_out.await;
+    }));
+
+    let client = WsClient::builder(&format!("ws://{}", addr))
+        .request_timeout(Duration::from_millis(100))
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(
+        Err(RpcError::Timeout),
+        client.call::<_, u64>("route_sleep", [1000]).await
+    );
+
+    // 服务端断开后等待中的请求结束
+    let call = tokio::spawn({
+        let client = client.clone();
+        async move { client.call::<_, u64>("route_sleep", [1000]).await }
+    });
+    time::delay_for(Duration::from_millis(50)).await;
+    signal_in.send(()).unwrap();
+    assert_eq!(Err(RpcError::Closed), call.await.unwrap());
+    assert!(client.is_closed());
+}
+
+#[tokio::test]
+async fn test_ws_client_batch() {
+    let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+    let addr = start_server(server, test_route()).await;
+    let client = WsClient::connect(&format!("ws://{}", addr)).await.unwrap();
+
+    let mut batch = client.batch();
+    let slow = batch.call::<_, u64>("route_sleep", [300]);
+    let fast = batch.call::<_, u64>("route_sleep", [10]);
+    let missing = batch.call::<_, u64>("route_missing", ());
+    let invalid = batch.call::<_, u64>("route_sleep", ["x"]);
+    batch.notify("route_sleep", [1]).unwrap();
+    assert_eq!(5, batch.len());
+    batch.send().unwrap();
+
+    // 每个调用分别得到自己的结果或错误
+    let (slow, fast, missing, invalid) =
+        futures_util::future::join4(slow, fast, missing, invalid).await;
+    assert_eq!(Ok(300), slow);
+    assert_eq!(Ok(10), fast);
+    match missing {
+        Err(RpcError::Rpc(err)) => assert_eq!(-32601, err.code),
+        _ => panic!("expect method not found"),
+    }
+    match invalid {
+        Err(RpcError::Rpc(err)) => assert_eq!(-32602, err.code),
+        _ => panic!("expect invalid params"),
+    }
+
+    // 空的批量调用不发送
+    client.batch().send().unwrap();
+
+    // 未发送的调用返回Closed
+    let mut batch = client.batch();
+    let unsent = batch.call::<_, u64>("route_sleep", [10]);
+    drop(batch);
+    assert_eq!(Err(RpcError::Closed), unsent.await);
+
+    client.close();
+    let mut batch = client.batch();
+    let closed = batch.call::<_, u64>("route_sleep", [10]);
+    assert_eq!(Err(RpcError::Closed), batch.send());
+    assert_eq!(Err(RpcError::Closed), closed.await);
+}
+
+#[tokio::test]
+async fn test_ws_client_subscribe() {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+    let addr = start_server(server, subscription_route(stopped.clone())).await;
+    let client = WsClient::connect(&format!("ws://{}", addr)).await.unwrap();
+
+    // 先于订阅响应到达的推送也不会丢失
+    let ticks = client
+        .subscribe::<_, u64>("subscribe_ticks", (), "unsubscribe_ticks")
+        .await
+        .unwrap();
+    assert!(ticks.id().is_string());
+    let ticks: Vec<_> = ticks.take(3).collect().await;
+    assert_eq!(vec![Ok(0), Ok(1), Ok(2)], ticks);
+
+    // 释放后取消订阅
+    wait_stopped(&stopped).await;
+
+    let mut ticks = client
+        .subscribe::<_, String>("subscribe_ticks", (), "unsubscribe_ticks")
+        .await
+        .unwrap();
+    match ticks.next().await {
+        Some(Err(RpcError::Parse(_))) => {}
+        _ => panic!("expect parse error"),
+    }
+
+    // 连接关闭后流产出Closed并结束
+    client.close();
+    loop {
+        match ticks.next().await {
+            Some(Err(RpcError::Parse(_))) => continue,
+            Some(Err(RpcError::Closed)) => break,
+            _ => panic!("expect closed"),
+        }
+    }
+    assert!(ticks.next().await.is_none());
+}
+
+// 启动可关闭的服务，关闭时不等待处理中的请求
+async fn start_stoppable_server(
+    addr: &str,
+) -> (
+    SocketAddr,
+    futures_channel::oneshot::Sender<()>,
+    tokio::task::JoinHandle<()>,
+) {
+    let server = WsServer::bind(addr.to_string())
+        .await
+        .unwrap()
+        .shutdown_timeout(Duration::from_millis(10));
+    let addr = server.local_addr().unwrap();
+    let (signal_in, signal_out) = futures_channel::oneshot::channel::<()>();
+    let listening = tokio::spawn(server.listen_with_shutdown(test_route(), async {
+        let _ = signal_out.await;
+    }));
+    (addr, signal_in, listening)
+}
+
+async fn reconnect_client(addr: SocketAddr, backoff: Backoff, policy: InFlightPolicy) -> WsClient {
+    WsClient::builder(&format!("ws://{}", addr))
+        .reconnect(backoff)
+        .in_flight_policy(policy)
+        .connect()
+        .await
+        .unwrap()
+}
+
+async fn wait_state(client: &WsClient, state: ConnectionState) {
+    for _ in 0..200 {
+        if client.state() == state {
+            return;
+        }
+        time::delay_for(Duration::from_millis(10)).await;
+    }
+    panic!("client state is not {:?}", state);
+}
+
+#[tokio::test]
+async fn test_ws_client_reconnect() {
+    let (addr, signal_in, listening) = start_stoppable_server("127.0.0.1:0").await;
+    let backoff = Backoff::new(Duration::from_millis(20), Duration::from_millis(100));
+
+    let wait_client = reconnect_client(addr, backoff.clone(), InFlightPolicy::Wait).await;
+    let fail_client = reconnect_client(addr, backoff.clone(), InFlightPolicy::Fail).await;
+    let replay_client = reconnect_client(addr, backoff.clone(), InFlightPolicy::Replay).await;
+    let retry_client = reconnect_client(
+        addr,
+        Backoff::new(Duration::from_millis(10), Duration::from_millis(10)).max_retries(1),
+        InFlightPolicy::Wait,
+    )
+    .await;
+    let mut states = wait_client.state_changes();
+    assert_eq!(Some(ConnectionState::Connected), states.next().await);
+
+    // 断开前已发出的调用
+    let idempotent = tokio::spawn({
+        let client = replay_client.clone();
+        async move { client.call_idempotent::<_, u64>("route_sleep", [300]).await }
+    });
+    let plain = tokio::spawn({
+        let client = replay_client.clone();
+        async move { client.call::<_, u64>("route_sleep", [300]).await }
+    });
+    time::delay_for(Duration::from_millis(50)).await;
+
+    signal_in.send(()).unwrap();
+    listening.await.unwrap();
+    assert_eq!(Some(ConnectionState::Reconnecting), states.next().await);
+    wait_state(&fail_client, ConnectionState::Reconnecting).await;
+
+    // Fail策略下重连期间的调用立即失败，Wait策略下等待重连
+    assert_eq!(
+        Err(RpcError::Closed),
+        fail_client.call::<_, u64>("route_sleep", [10]).await
+    );
+    let waiting = tokio::spawn({
+        let client = wait_client.clone();
+        async move { client.call::<_, u64>("route_sleep", [10]).await }
+    });
+
+    // 重连次数用尽后关闭
+    let retry_states: Vec<_> = time::timeout(
+        Duration::from_secs(2),
+        retry_client.state_changes().collect(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(ConnectionState::Closed, *retry_states.last().unwrap());
+    assert!(retry_client.is_closed());
+
+    let (_, _signal_in, _listening) = start_stoppable_server(&addr.to_string()).await;
+    assert_eq!(Some(ConnectionState::Connected), states.next().await);
+
+    assert_eq!(Ok(10), waiting.await.unwrap());
+    assert_eq!(Ok(300), idempotent.await.unwrap());
+    assert_eq!(Err(RpcError::Closed), plain.await.unwrap());
+
+    wait_state(&fail_client, ConnectionState::Connected).await;
+    assert_eq!(
+        Ok(10),
+        fail_client.call::<_, u64>("route_sleep", [10]).await
+    );
+
+    wait_client.close();
+    assert_eq!(Some(ConnectionState::Closed), states.next().await);
+    assert_eq!(None, states.next().await);
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test]
+async fn test_ws_tls() {
+    use tokio_rustls::rustls::{Certificate, ClientConfig};
+    use tokio_rustls::webpki::DNSNameRef;
+    use tokio_rustls::TlsConnector;
+
+    // 测试时生成自签名证书
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let server = WsServer::bind("127.0.0.1:0".to_string())
+        .await
+        .unwrap()
+        .tls_pem(
+            cert.serialize_pem().unwrap().as_bytes(),
+            cert.serialize_private_key_pem().as_bytes(),
+        )
+        .unwrap();
+    let addr = start_server(server, test_route()).await;
+
+    let mut config = ClientConfig::new();
+    config
+        .root_store
+        .add(&Certificate(cert.serialize_der().unwrap()))
+        .unwrap();
+    let connector = TlsConnector::from(Arc::new(config));
+    let tcp_stream = TcpStream::connect(addr).await.unwrap();
+    let tls_stream = connector
+        .connect(
+            DNSNameRef::try_from_ascii_str("localhost").unwrap(),
+            tcp_stream,
+        )
+        .await
+        .unwrap();
+    let (mut ws_stream, _) = tokio_tungstenite::client_async("wss://localhost/", tls_stream)
+        .await
+        .unwrap();
+
+    ws_stream
+        .send(Message::Text(
+            json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [10], "id": 1}).to_string(),
+        ))
+        .await
+        .unwrap();
+    let resp = loop {
+        if let Message::Text(msg) = ws_stream.next().await.unwrap().unwrap() {
+            break serde_json::from_str::<Value>(&msg).unwrap();
+        }
+    };
+    assert_eq!(json!({"jsonrpc": "2.0", "result": 10, "id": 1}), resp);
+
+    // 未经TLS的连接握手失败
+    assert!(
+        connect_async(url::Url::parse(&format!("ws://{}", addr)).unwrap())
+            .await
+            .is_err()
+    );
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test]
+async fn test_ws_tls_bad_pem() {
+    let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+    assert!(server.tls_pem(b"not a cert", b"not a key").is_err());
+}
+
+#[cfg(feature = "http")]
+async fn http_post(addr: SocketAddr, body: &str) -> (hyper::StatusCode, Option<String>, String) {
+    let req = hyper::Request::post(format!("http://{}/", addr))
+        .header("content-type", "application/json")
+        .body(hyper::Body::from(body.to_string()))
+        .unwrap();
+    let resp = hyper::Client::new().request(req).await.unwrap();
+    let status = resp.status();
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .map(|content_type| content_type.to_str().unwrap().to_string());
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    (
+        status,
+        content_type,
+        String::from_utf8(body.to_vec()).unwrap(),
+    )
+}
+
+#[cfg(feature = "http")]
+#[tokio::test]
+async fn test_http() {
+    let server = WsServer::bind("127.0.0.1:0".to_string())
+        .await
+        .unwrap()
+        .http()
+        .max_body_size(1024);
+    let addr = start_server(server, test_route()).await;
+
+    let (status, content_type, body) = http_post(
+        addr,
+        r#"{"jsonrpc": "2.0", "method": "route_sleep", "params": [1], "id": 1}"#,
+    )
+    .await;
+    assert_eq!(hyper::StatusCode::OK, status);
+    assert_eq!(Some("application/json".to_string()), content_type);
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": 1, "id": 1}),
+        serde_json::from_str::<Value>(&body).unwrap()
+    );
+
+    let (status, _, body) = http_post(
+        addr,
+        r#"[{"jsonrpc": "2.0", "method": "route_sleep", "params": [1], "id": 1},
+            {"jsonrpc": "2.0", "method": "route_sleep", "params": [2]}]"#,
+    )
+    .await;
+    assert_eq!(hyper::StatusCode::OK, status);
+    assert_eq!(
+        json!([{"jsonrpc": "2.0", "result": 1, "id": 1}]),
+        serde_json::from_str::<Value>(&body).unwrap()
+    );
+
+    // 解析错误同样以JSON-RPC错误响应
+    let (status, _, body) = http_post(addr, "{").await;
+    assert_eq!(hyper::StatusCode::OK, status);
+    assert_eq!(
+        -32700,
+        serde_json::from_str::<Value>(&body).unwrap()["error"]["code"]
+    );
+
+    // 只有通知时没有响应内容
+    let (status, _, body) = http_post(
+        addr,
+        r#"{"jsonrpc": "2.0", "method": "route_sleep", "params": [1]}"#,
+    )
+    .await;
+    assert_eq!(hyper::StatusCode::NO_CONTENT, status);
+    assert!(body.is_empty());
+
+    // 非UTF-8内容按解析错误返回
+    let req = hyper::Request::post(format!("http://{}/", addr))
+        .body(hyper::Body::from(
+            &b"{\"jsonrpc\": \"2.0\", \"method\": \"route_sleep\", \"params\": [\"\xff\"], \"id\": 1}"[..],
+        ))
+        .unwrap();
+    let resp = hyper::Client::new().request(req).await.unwrap();
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(
+        -32700,
+        serde_json::from_slice::<Value>(&body).unwrap()["error"]["code"]
+    );
+
+    // 请求体超过上限
+    let (status, _, _) = http_post(addr, &" ".repeat(1025)).await;
+    assert_eq!(hyper::StatusCode::PAYLOAD_TOO_LARGE, status);
+
+    let resp = hyper::Client::new()
+        .get(format!("http://{}/", addr).parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(hyper::StatusCode::METHOD_NOT_ALLOWED, resp.status());
+    assert_eq!("POST", resp.headers()["allow"]);
+
+    // WebSocket共用同一端口
+    let mut ws_stream = connect(addr).await;
+    send(
+        &mut ws_stream,
+        json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [1], "id": 2}),
+    )
+    .await;
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": 1, "id": 2}),
+        recv(&mut ws_stream).await
+    );
+}
+
+#[cfg(unix)]
+mod ipc {
+    use super::*;
+    use jsonrpc_websocket::IpcServer;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixStream;
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("jsonrpc-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn test_ipc() {
+        let path = socket_path("ipc");
+        let server = IpcServer::bind_with_permissions(&path, 0o600).unwrap();
+        assert_eq!(
+            0o600,
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777
+        );
+        // 绑定用的临时目录已删除
+        let mut private_dir = path.clone().into_os_string();
+        private_dir.push(format!(".{}.tmp", std::process::id()));
+        assert!(!PathBuf::from(private_dir).exists());
+        tokio::spawn(server.listen_loop(test_route()));
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (read_half, mut write_half) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read_half).lines();
+
+        // 一次写入多行，按完成顺序返回
+        let reqs = format!(
+            "{}\n{}\n",
+            json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [300], "id": "slow"}),
+            json!([{"jsonrpc": "2.0", "method": "route_sleep", "params": [10], "id": "fast"}]),
+        );
+        write_half.write_all(reqs.as_bytes()).await.unwrap();
+
+        let resp: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(
+            json!([{"jsonrpc": "2.0", "result": 10, "id": "fast"}]),
+            resp
+        );
+        let resp: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(json!({"jsonrpc": "2.0", "result": 300, "id": "slow"}), resp);
+    }
+
+    #[tokio::test]
+    async fn test_ipc_shutdown() {
+        let path = socket_path("shutdown");
+        let server = IpcServer::bind(&path).unwrap();
+        let (signal_in, signal_out) = futures_channel::oneshot::channel::<()>();
+        let listening = tokio::spawn(server.listen_with_shutdown(test_route(), async {
+            let _ = signal_out.await;
+        }));
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (read_half, mut write_half) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read_half).lines();
+        let req = json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [300], "id": 1});
+        write_half
+            .write_all(format!("{}\n", req).as_bytes())
+            .await
+            .unwrap();
+        time::delay_for(Duration::from_millis(50)).await;
+        signal_in.send(()).unwrap();
+
+        // 处理中的请求完成前套接字文件保留
+        time::delay_for(Duration::from_millis(50)).await;
+        assert!(path.exists());
+        let resp: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(json!({"jsonrpc": "2.0", "result": 300, "id": 1}), resp);
+
+        time::timeout(Duration::from_secs(1), listening)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_ipc_message_too_large() {
+        let path = socket_path("large");
+        let server = IpcServer::bind(&path).unwrap().max_message_size(64);
+        tokio::spawn(server.listen_loop(test_route()));
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (read_half, mut write_half) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read_half).lines();
+
+        // 不超过上限的行正常处理，非UTF-8内容以解析错误响应
+        let req = json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [1], "id": 1});
+        write_half
+            .write_all(format!("{}\n", req).as_bytes())
+            .await
+            .unwrap();
+        let resp: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(json!({"jsonrpc": "2.0", "result": 1, "id": 1}), resp);
+        write_half.write_all(b"[\"\xff\"]\n").await.unwrap();
+        let resp: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(-32700, resp["error"]["code"]);
+
+        // 超过上限时断开连接
+        let _ = write_half.write_all(" ".repeat(1024).as_bytes()).await;
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ipc_stale_socket() {
+        let path = socket_path("stale");
+
+        // 进程退出后残留的套接字文件
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let server = IpcServer::bind(&path).unwrap();
+
+        // 正在监听的套接字不能被占用
+        assert!(IpcServer::bind(&path).is_err());
+
+        // 关闭后删除套接字文件
+        drop(server);
+        assert!(!path.exists());
+
+        // 不是套接字的文件不删除
+        std::fs::write(&path, b"data").unwrap();
+        assert!(IpcServer::bind(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(unix)]
+mod stdio {
+    use super::*;
+    use jsonrpc_websocket::StdioServer;
+    use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixStream;
+
+    fn frame(msg: Value) -> String {
+        let msg = msg.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", msg.len(), msg)
+    }
+
+    async fn read_frame<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Value {
+        let mut header = String::new();
+        reader.read_line(&mut header).await.unwrap();
+        let len: usize = header
+            .trim()
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut blank = String::new();
+        reader.read_line(&mut blank).await.unwrap();
+        assert_eq!("\r\n", blank);
+
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stdio() {
+        let (server_io, client_io) = UnixStream::pair().unwrap();
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let serving = tokio::spawn(
+            StdioServer::with_io(server_read, server_write)
+                .serve(subscription_route(stopped.clone())),
+        );
+
+        let (client_read, mut client_write) = tokio::io::split(client_io);
+        let mut client_read = BufReader::new(client_read);
+
+        // 额外的头部忽略
+        let msg = json!({"jsonrpc": "2.0", "method": "subscribe_ticks", "id": 1}).to_string();
+        let req = format!(
+            "Content-Type: application/vscode-jsonrpc; charset=utf-8\r\ncontent-length: {}\r\n\r\n{}",
+            msg.len(),
+            msg
+        );
+        client_write.write_all(req.as_bytes()).await.unwrap();
+
+        // 订阅响应与推送通知
+        let mut sub_id = Value::Null;
+        let mut ticks = 0;
+        while sub_id.is_null() || ticks < 2 {
+            let msg = read_frame(&mut client_read).await;
+            if msg["id"] == 1 {
+                sub_id = msg["result"].clone();
+            } else {
+                assert_eq!("ticks", msg["method"]);
+                ticks += 1;
+            }
+        }
+
+        client_write
+            .write_all(
+                frame(json!({"jsonrpc": "2.0", "method": "unsubscribe_ticks", "params": [sub_id], "id": 2}))
+                    .as_bytes(),
+            )
+            .await
+            .unwrap();
+        loop {
+            let msg = read_frame(&mut client_read).await;
+            if msg["id"] == 2 {
+                assert_eq!(true, msg["result"]);
+                break;
+            }
+        }
+        wait_stopped(&stopped).await;
+
+        // 输入结束后服务返回
+        client_write.shutdown().await.unwrap();
+        time::timeout(Duration::from_secs(1), serving)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stdio_eof_flush() {
+        let (server_io, client_io) = UnixStream::pair().unwrap();
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let serving =
+            tokio::spawn(StdioServer::with_io(server_read, server_write).serve(test_route()));
+
+        let (client_read, mut client_write) = tokio::io::split(client_io);
+        let mut client_read = BufReader::new(client_read);
+
+        // 写入请求后立即结束输入，响应仍然写回
+        client_write
+            .write_all(
+                frame(json!({"jsonrpc": "2.0", "method": "route_sleep", "params": [100], "id": 1}))
+                    .as_bytes(),
+            )
+            .await
+            .unwrap();
+        client_write.shutdown().await.unwrap();
+
+        assert_eq!(
+            json!({"jsonrpc": "2.0", "result": 100, "id": 1}),
+            read_frame(&mut client_read).await
+        );
+        serving.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stdio_bad_frame() {
+        let (server_io, client_io) = UnixStream::pair().unwrap();
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let serving =
+            tokio::spawn(StdioServer::with_io(server_read, server_write).serve(test_route()));
+
+        let (_client_read, mut client_write) = tokio::io::split(client_io);
+        client_write
+            .write_all(b"not a header\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(serving.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stdio_frame_too_large() {
+        let (server_io, client_io) = UnixStream::pair().unwrap();
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let serving = tokio::spawn(
+            StdioServer::with_io(server_read, server_write)
+                .max_message_size(64)
+                .serve(test_route()),
+        );
+
+        let (_client_read, mut client_write) = tokio::io::split(client_io);
+        client_write
+            .write_all(b"Content-Length: 18446744073709551615\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(serving.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stdio_header_too_long() {
+        let (server_io, client_io) = UnixStream::pair().unwrap();
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let serving =
+            tokio::spawn(StdioServer::with_io(server_read, server_write).serve(test_route()));
+
+        // 头部一直不换行
+        let (_client_read, mut client_write) = tokio::io::split(client_io);
+        let header = format!("Content-Length: 1{}", " ".repeat(8192));
+        let _ = client_write.write_all(header.as_bytes()).await;
+        assert!(serving.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stdio_invalid_utf8() {
+        let (server_io, client_io) = UnixStream::pair().unwrap();
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let serving =
+            tokio::spawn(StdioServer::with_io(server_read, server_write).serve(test_route()));
+
+        let (client_read, mut client_write) = tokio::io::split(client_io);
+        let mut client_read = BufReader::new(client_read);
+        let body = &b"{\"jsonrpc\": \"2.0\", \"method\": \"route_sleep\", \"params\": [\"\xff\"], \"id\": 1}"[..];
+        let frame = [
+            format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes(),
+            body,
+        ]
+        .concat();
+        client_write.write_all(&frame).await.unwrap();
+        client_write.shutdown().await.unwrap();
+
+        assert_eq!(
+            json!({"jsonrpc": "2.0", "error": JsonRpcError::parse_error(), "id": null}),
+            read_frame(&mut client_read).await
+        );
+        serving.await.unwrap().unwrap();
+    }
+}
+
+mod derive {
+    use super::*;
+    use jsonrpc_derive::rpc;
+    use serde::Deserialize;
+    use std::sync::atomic::AtomicU64;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Pair {
+        a: u64,
+        b: u64,
+    }
+
+    #[rpc(namespace = "calc")]
+    pub trait CalcApi {
+        /// 两数相加
+        async fn add(&self, a: u64, b: u64) -> Result<u64, TestError>;
+
+        #[rpc(name = "pair.sum")]
+        async fn sum(&self, pair: Pair) -> Result<u64, TestError>;
+
+        async fn div(&self, a: u64, b: u64) -> Result<u64, JsonRpcError>;
+
+        async fn count(&self) -> Result<u64, TestError>;
+
+        async fn echo(&self, values: Vec<Value>) -> Result<Vec<Value>, TestError>;
+
+        async fn nested(&self, values: Vec<Vec<u64>>) -> Result<usize, TestError>;
+    }
+
+    #[derive(Default)]
+    struct Calc {
+        count: AtomicU64,
+    }
+
+    impl CalcApi for Calc {
+        async fn add(&self, a: u64, b: u64) -> Result<u64, TestError> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(a + b)
+        }
+
+        async fn sum(&self, pair: Pair) -> Result<u64, TestError> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            time::delay_for(Duration::from_millis(10)).await;
+            Ok(pair.a + pair.b)
+        }
+
+        async fn div(&self, a: u64, b: u64) -> Result<u64, JsonRpcError> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            a.checked_div(b).ok_or_else(JsonRpcError::invalid_params)
+        }
+
+        async fn count(&self) -> Result<u64, TestError> {
+            Ok(self.count.load(Ordering::SeqCst))
+        }
+
+        async fn echo(&self, values: Vec<Value>) -> Result<Vec<Value>, TestError> {
+            Ok(values)
+        }
+
+        async fn nested(&self, values: Vec<Vec<u64>>) -> Result<usize, TestError> {
+            Ok(values.len())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rpc_trait() {
+        let route = Arc::new(Calc::default().into_route(Route::new()).build().unwrap());
+        let server = WsServer::bind("127.0.0.1:0".to_string()).await.unwrap();
+        let addr = start_server(server, route).await;
+        let client =
+            CalcApiClient::new(WsClient::connect(&format!("ws://{}", addr)).await.unwrap());
+
+        assert_eq!(Ok(3), client.add(1, 2).await);
+        assert_eq!(Ok(7), client.sum(Pair { a: 3, b: 4 }).await);
+        assert_eq!(Ok(2), client.div(4, 2).await);
+        match client.div(4, 0).await {
+            Err(RpcError::Rpc(err)) => assert_eq!(-32602, err.code),
+            _ => panic!("expect invalid params"),
+        }
+        assert_eq!(Ok(4), client.count().await);
+
+        // 单个参数本身为数组时不会被展开
+        assert_eq!(
+            Ok(vec![json!(1), json!(2)]),
+            client.echo(vec![json!(1), json!(2)]).await
+        );
+        assert_eq!(Ok(0), client.nested(vec![]).await);
+        assert_eq!(Ok(2), client.nested(vec![vec![1], vec![]]).await);
+
+        // 方法名带namespace，参数只按位置解析
+        let sum: u64 = client
+            .client()
+            .call("calc.pair.sum", [json!({"a": 5, "b": 6})])
+            .await
+            .unwrap();
+        assert_eq!(11, sum);
+        match client
+            .client()
+            .call::<_, u64>("calc.pair.sum", json!({"a": 5, "b": 6}))
+            .await
+        {
+            Err(RpcError::Rpc(err)) => assert_eq!(-32602, err.code),
+            _ => panic!("expect invalid params"),
+        }
+        match client.client().call::<_, u64>("add", [1, 2]).await {
+            Err(RpcError::Rpc(err)) => assert_eq!(-32601, err.code),
+            _ => panic!("expect method not found"),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::data::{DataExtensions, DataFactory};
+use std::sync::Arc;
+
+/// 连接级别的上下文
+///   由传输层在连接建立时创建，同一连接上的请求共享
+#[derive(Clone, Default)]
+pub struct Context(Arc<DataExtensions>);
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 放入连接级别的数据，只能在Context被共享之前调用
+    pub fn with<T: Clone + Send + Sync + 'static>(mut self, t: T) -> Self {
+        Arc::get_mut(&mut self.0).unwrap().insert(t);
+        self
+    }
+
+    pub fn get<T: Clone + 'static>(&self) -> Option<&T> {
+        self.0.get::<T>()
+    }
+}
@@ -1,5 +1,5 @@
 use std::any::{Any, TypeId};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use fxhash::FxHashMap;
 
@@ -25,29 +25,48 @@ pub(crate) trait DataFactory {
     fn get<D: Clone + 'static>(&self) -> Option<&D>;
 }
 
-pub struct DataExtensions(FxHashMap<TypeId, Box<dyn Any>>);
+/// 共享数据集合，挂载的子Route在自身找不到时到父Route中查找
+#[derive(Default)]
+pub struct DataExtensions {
+    map: FxHashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    parent: OnceLock<Arc<DataExtensions>>,
+}
 
 impl DataExtensions {
-    pub fn insert<T: Clone + 'static>(&mut self, t: T) {
-        self.0.insert(TypeId::of::<T>(), Box::new(t));
+    pub fn insert<T: Clone + Send + Sync + 'static>(&mut self, t: T) {
+        self.map.insert(TypeId::of::<T>(), Box::new(t));
     }
-}
 
-unsafe impl Sync for DataExtensions {}
-
-unsafe impl Send for DataExtensions {}
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.map.contains_key(&type_id)
+            || self
+                .parent
+                .get()
+                .is_some_and(|parent| parent.contains(type_id))
+    }
 
-impl Default for DataExtensions {
-    fn default() -> Self {
-        Self(FxHashMap::<TypeId, Box<dyn Any>>::default())
+    /// 沿父级链设置最外层的父级，parent已在链上时不设置
+    pub(crate) fn set_root_parent(self: &Arc<Self>, parent: &Arc<DataExtensions>) {
+        let mut top = self;
+        loop {
+            if Arc::ptr_eq(top, parent) {
+                return;
+            }
+            match top.parent.get() {
+                Some(next) => top = next,
+                None => break,
+            }
+        }
+        let _ = top.parent.set(parent.clone());
     }
 }
 
 impl DataFactory for DataExtensions {
     fn get<D: Clone + 'static>(&self) -> Option<&D> {
-        self.0
+        self.map
             .get(&TypeId::of::<D>())
             .and_then(|boxed| boxed.downcast_ref())
+            .or_else(|| self.parent.get().and_then(|parent| parent.get()))
     }
 }
 
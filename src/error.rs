@@ -0,0 +1,36 @@
+use std::error::Error;
+use std::fmt;
+
+/// Route构建时发现的配置错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    /// 处理函数依赖的 `Data<T>` 没有注册，(方法名, 缺少的类型名列表)
+    MissingData(Vec<(String, Vec<&'static str>)>),
+    /// 方法名重复注册(包括挂载子Route时与已有方法冲突)，重复的方法名列表
+    DuplicateMethod(Vec<String>),
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::MissingData(missing) => {
+                let missing: Vec<String> = missing
+                    .iter()
+                    .map(|(method, types)| {
+                        format!("method {} require data [{}]", method, types.join(", "))
+                    })
+                    .collect();
+                write!(f, "{}, register it by Route::data", missing.join("; "))
+            }
+            RouteError::DuplicateMethod(methods) => {
+                write!(
+                    f,
+                    "method [{}] registered more than once",
+                    methods.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl Error for RouteError {}
@@ -0,0 +1,158 @@
+use crate::context::Context;
+use crate::data::{Data, DataExtensions, DataFactory};
+use crate::id::Id;
+use crate::params::from_params;
+use crate::route::Request;
+use crate::session::{subscription_unsupported_error, Session, Sink};
+use jsonrpc_lite::Error as JsonRpcError;
+use serde::de::DeserializeOwned;
+use std::any::{type_name, TypeId};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// 提取器的输入，包含请求本身、Route上的共享数据以及连接上下文
+pub struct RequestParts {
+    pub request: Request,
+    pub context: Context,
+    pub(crate) extensions: Arc<DataExtensions>,
+    // 订阅方法调用时创建的推送端
+    pub(crate) sink: Option<Sink>,
+}
+
+impl RequestParts {
+    pub fn data<T: 'static>(&self) -> Option<&Data<T>> {
+        self.extensions.get::<Data<T>>()
+    }
+}
+
+/// 处理函数参数的提取器
+///   提取失败时直接以返回的错误响应该请求
+pub trait FromRequest: Sized {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError>;
+
+    /// 提取器依赖的 `Data<T>` 类型及类型名，注册处理函数时校验是否已经注册
+    fn required_data() -> Vec<(TypeId, &'static str)> {
+        Vec::new()
+    }
+}
+
+impl<T: 'static> FromRequest for Data<T> {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+        match req.data::<T>() {
+            Some(data) => Ok(data.clone()),
+            None => {
+                log::error!(
+                    "method {} require data {}, which is not registered",
+                    req.request.method,
+                    type_name::<T>()
+                );
+                Err(JsonRpcError::internal_error())
+            }
+        }
+    }
+
+    fn required_data() -> Vec<(TypeId, &'static str)> {
+        vec![(TypeId::of::<Data<T>>(), type_name::<T>())]
+    }
+}
+
+/// 类型化的请求参数，解析规则见 `params` 模块
+pub struct Params<P>(pub P);
+
+impl<P> Params<P> {
+    pub fn into_inner(self) -> P {
+        self.0
+    }
+}
+
+impl<P> Deref for Params<P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        &self.0
+    }
+}
+
+impl<P: DeserializeOwned> FromRequest for Params<P> {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+        from_params(req.request.params.clone())
+            .map(Params)
+            .map_err(|_| JsonRpcError::invalid_params())
+    }
+}
+
+/// 请求id，通知类请求没有id，需要使用 `Option<Id>`
+impl FromRequest for Id {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+        req.request
+            .id
+            .clone()
+            .ok_or_else(JsonRpcError::invalid_request)
+    }
+}
+
+/// 请求的方法名
+pub struct Method(pub String);
+
+impl FromRequest for Method {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+        Ok(Method(req.request.method.clone()))
+    }
+}
+
+impl FromRequest for Context {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+        Ok(req.context.clone())
+    }
+}
+
+/// 连接会话，可以向对端推送通知或发出请求，需要传输层支持
+impl FromRequest for Session {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+        req.context
+            .get::<Session>()
+            .cloned()
+            .ok_or_else(subscription_unsupported_error)
+    }
+}
+
+/// 订阅的推送端，只能在 `Route::subscription` 注册的处理函数中使用
+impl FromRequest for Sink {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+        req.sink.clone().ok_or_else(subscription_unsupported_error)
+    }
+}
+
+impl<T: FromRequest> FromRequest for Option<T> {
+    fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+        Ok(T::from_request(req).ok())
+    }
+}
+
+macro_rules! tuple_from_request ({ $($T:ident)* } => {
+    impl<$($T: FromRequest),*> FromRequest for ($($T,)*) {
+        #[allow(unused_variables)]
+        fn from_request(req: &RequestParts) -> Result<Self, JsonRpcError> {
+            Ok(($($T::from_request(req)?,)*))
+        }
+
+        #[allow(unused_mut)]
+        fn required_data() -> Vec<(TypeId, &'static str)> {
+            let mut required = Vec::new();
+            $(required.extend($T::required_data());)*
+            required
+        }
+    }
+});
+
+tuple_from_request!();
+tuple_from_request!(A);
+tuple_from_request!(A B);
+tuple_from_request!(A B C);
+tuple_from_request!(A B C D);
+tuple_from_request!(A B C D E);
+tuple_from_request!(A B C D E F);
+tuple_from_request!(A B C D E F G);
+tuple_from_request!(A B C D E F G H);
+tuple_from_request!(A B C D E F G H I);
+tuple_from_request!(A B C D E F G H I J);
@@ -1,8 +1,8 @@
-use crate::data::Data;
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use std::future::Future;
 
-pub(crate) trait Factory<T, R, O>: Clone + 'static
+/// 处理函数，参数T为提取器元组
+pub trait Factory<T, R, O>: Clone + 'static
 where
     O: Serialize,
     R: Future<Output = O> + Send,
@@ -10,15 +10,28 @@ where
     fn call(&self, params: T) -> R;
 }
 
-impl<F, R, O, T, P> Factory<(Data<T>, P), R, O> for F
-where
-    F: Fn(Data<T>, P) -> R + Clone + 'static,
-    O: Serialize,
-    R: Future<Output = O> + Send,
-    P: for<'de> Deserialize<'de>,
-    T: 'static,
-{
-    fn call(&self, params: (Data<T>, P)) -> R {
-        (self)(params.0, params.1)
+macro_rules! factory_tuple ({ $($param:ident)* } => {
+    impl<Func, $($param,)* Res, O> Factory<($($param,)*), Res, O> for Func
+    where
+        Func: Fn($($param),*) -> Res + Clone + 'static,
+        O: Serialize,
+        Res: Future<Output = O> + Send,
+    {
+        #[allow(non_snake_case)]
+        fn call(&self, ($($param,)*): ($($param,)*)) -> Res {
+            (self)($($param,)*)
+        }
     }
-}
+});
+
+factory_tuple!();
+factory_tuple!(A);
+factory_tuple!(A B);
+factory_tuple!(A B C);
+factory_tuple!(A B C D);
+factory_tuple!(A B C D E);
+factory_tuple!(A B C D E F);
+factory_tuple!(A B C D E F G);
+factory_tuple!(A B C D E F G H);
+factory_tuple!(A B C D E F G H I);
+factory_tuple!(A B C D E F G H I J);
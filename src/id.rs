@@ -0,0 +1,123 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Number, Value};
+use std::fmt;
+
+/// json-rpc请求id，取值为 String | Number | Null
+///   数字按原始精度保存，响应时原样返回
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Id {
+    Null,
+    Number(Number),
+    Str(String),
+}
+
+impl Id {
+    /// 从原始请求中尽量取出id，没有id或者id类型错误时返回None
+    pub fn of(req: &Value) -> Option<Id> {
+        match req.get("id")? {
+            Value::Null => Some(Id::Null),
+            Value::Number(num) => Some(Id::Number(num.clone())),
+            Value::String(s) => Some(Id::Str(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl From<u64> for Id {
+    fn from(num: u64) -> Self {
+        Id::Number(num.into())
+    }
+}
+
+impl From<i64> for Id {
+    fn from(num: i64) -> Self {
+        Id::Number(num.into())
+    }
+}
+
+impl From<String> for Id {
+    fn from(s: String) -> Self {
+        Id::Str(s)
+    }
+}
+
+impl From<&str> for Id {
+    fn from(s: &str) -> Self {
+        Id::Str(s.to_string())
+    }
+}
+
+impl From<Id> for Value {
+    fn from(id: Id) -> Self {
+        match id {
+            Id::Null => Value::Null,
+            Id::Number(num) => Value::Number(num),
+            Id::Str(s) => Value::String(s),
+        }
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Id::Null => write!(f, "null"),
+            Id::Number(num) => write!(f, "{}", num),
+            Id::Str(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+impl Serialize for Id {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Id::Null => serializer.serialize_unit(),
+            Id::Number(num) => num.serialize(serializer),
+            Id::Str(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Null => Ok(Id::Null),
+            Value::Number(num) => Ok(Id::Number(num)),
+            Value::String(s) => Ok(Id::Str(s)),
+            _ => Err(serde::de::Error::custom(
+                "id must be a string, number or null",
+            )),
+        }
+    }
+}
+
+/// 区分 `"id": null` 与缺少id的通知
+pub(crate) fn deserialize_some<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Id>, D::Error> {
+    Id::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_id_verbatim() {
+        let raw = r#"[12345678901234567890123456789,1.50,"abc-uuid",null,-7]"#;
+        let ids: Vec<Id> = serde_json::from_str(raw).unwrap();
+
+        assert_eq!(Id::Str("abc-uuid".to_string()), ids[2]);
+        assert_eq!(Id::Null, ids[3]);
+        assert_eq!(Id::from(-7i64), ids[4]);
+        assert_eq!(raw, serde_json::to_string(&ids).unwrap());
+    }
+
+    #[test]
+    fn test_id_of() {
+        assert_eq!(Some(Id::from(1u64)), Id::of(&json!({"id": 1})));
+        assert_eq!(Some(Id::Null), Id::of(&json!({"id": null})));
+        assert_eq!(None, Id::of(&json!({"method": "a"})));
+        assert_eq!(None, Id::of(&json!({"id": [1]})));
+    }
+}
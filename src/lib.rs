@@ -1,13 +1,31 @@
-#![feature(type_alias_impl_trait)]
-#![feature(fn_traits)]
-
 mod data;
 pub use data::Data;
 
+mod context;
+pub use context::Context;
+
+pub mod extract;
+pub use extract::{Method, Params};
+
+mod error;
+pub use error::RouteError;
+
 mod factory;
+pub use factory::Factory;
+
+mod id;
+pub use id::Id;
+
+mod middleware;
+pub use middleware::Middleware;
+
+mod params;
 
 pub mod route;
 
+mod session;
+pub use session::{CallError, Session, SessionClosed, Sink};
+
 use jsonrpc_lite::Error as JsonRpcError;
 
 fn server_route_error() -> JsonRpcError {
@@ -0,0 +1,20 @@
+use crate::context::Context;
+use crate::route::Request;
+use jsonrpc_lite::Error as JsonRpcError;
+use serde_json::Value;
+
+/// 中间件，在 `Route` 分发请求前后执行，用于日志、鉴权、统计、限流等
+///   `on_request` 按注册顺序执行，`on_response` 按逆序执行
+///   批量请求中的每个请求分别经过中间件，非法请求不经过中间件
+///   中间件panic时与处理函数一样以-32603 Internal error响应，不影响同批次的其他请求
+pub trait Middleware: Send + Sync + 'static {
+    /// 查找处理函数前调用，可以修改请求(包括方法名)
+    ///   返回Err时不再执行之后的中间件与处理函数，以该错误响应
+    fn on_request(&self, _req: &mut Request, _context: &Context) -> Result<(), JsonRpcError> {
+        Ok(())
+    }
+
+    /// 响应返回前调用，可以修改响应；通知类请求没有响应，不会调用
+    ///   只有 `on_request` 成功的中间件会被调用
+    fn on_response(&self, _method: &str, _resp: &mut Value) {}
+}
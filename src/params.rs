@@ -0,0 +1,98 @@
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// 将请求中的params解析为处理函数的参数
+///   params缺省时依次尝试按 null、{}、[] 解析，即 () / Option / #[serde(default)]结构体 / Vec
+///   结构体参数同时支持按名称的对象与按字段顺序的数组
+///   空数组、空对象按缺省处理，只有一个元素的数组可以直接解析为单个参数
+pub(crate) fn from_params<P: DeserializeOwned>(
+    params: Option<Value>,
+) -> Result<P, serde_json::Error> {
+    let params = match params {
+        Some(params) => params,
+        None => return from_omitted(),
+    };
+
+    let err = match serde_json::from_value(params.clone()) {
+        Ok(params) => return Ok(params),
+        Err(err) => err,
+    };
+
+    match params {
+        Value::Array(ref array) if array.is_empty() => from_omitted().map_err(|_| err),
+        Value::Object(ref obj) if obj.is_empty() => from_omitted().map_err(|_| err),
+        Value::Array(mut array) if array.len() == 1 => {
+            serde_json::from_value(array.pop().unwrap()).map_err(|_| err)
+        }
+        _ => Err(err),
+    }
+}
+
+fn from_omitted<P: DeserializeOwned>() -> Result<P, serde_json::Error> {
+    serde_json::from_value(Value::Null)
+        .or_else(|_| serde_json::from_value(Value::Object(Map::new())))
+        .or_else(|_| serde_json::from_value(Value::Array(Vec::new())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Named {
+        a: u64,
+        b: String,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq, Default)]
+    #[serde(default)]
+    struct Defaulted {
+        a: u64,
+        b: Option<String>,
+    }
+
+    #[test]
+    fn test_params_named_and_positional() {
+        let expect = Named {
+            a: 1,
+            b: "x".to_string(),
+        };
+        assert_eq!(
+            expect,
+            from_params::<Named>(Some(json!({"a": 1, "b": "x"}))).unwrap()
+        );
+        assert_eq!(expect, from_params::<Named>(Some(json!([1, "x"]))).unwrap());
+        assert!(from_params::<Named>(Some(json!([1]))).is_err());
+        assert!(from_params::<Named>(None).is_err());
+
+        assert_eq!(
+            Defaulted { a: 1, b: None },
+            from_params::<Defaulted>(Some(json!([1]))).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_params_omitted() {
+        from_params::<()>(None).unwrap();
+        from_params::<()>(Some(json!([]))).unwrap();
+        from_params::<()>(Some(json!({}))).unwrap();
+        assert_eq!(None, from_params::<Option<u64>>(None).unwrap());
+        assert_eq!(
+            Defaulted::default(),
+            from_params::<Defaulted>(None).unwrap()
+        );
+        assert_eq!(Vec::<u64>::new(), from_params::<Vec<u64>>(None).unwrap());
+    }
+
+    #[test]
+    fn test_params_single() {
+        assert_eq!(5u64, from_params::<u64>(Some(json!([5]))).unwrap());
+        assert_eq!(
+            (5u64, "x".to_string()),
+            from_params::<(u64, String)>(Some(json!([5, "x"]))).unwrap()
+        );
+        assert!(from_params::<u64>(Some(json!({"a": 5}))).is_err());
+    }
+}
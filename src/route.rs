@@ -1,13 +1,23 @@
-use crate::data::DataFactory;
+use crate::context::Context;
 use crate::data::{Data, DataExtensions};
+use crate::error::RouteError;
+use crate::extract::Params;
+use crate::extract::{FromRequest, RequestParts};
+use crate::factory::Factory;
+use crate::id::{deserialize_some, Id};
+use crate::middleware::Middleware;
 use crate::server_route_error;
+use crate::session::{subscription_unsupported_error, Session};
 use futures_util::future::join_all;
+use futures_util::FutureExt;
 use jsonrpc_lite::Error as JsonRpcError;
-use jsonrpc_lite::JsonRpc;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::any::{Any, TypeId};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 
@@ -15,127 +25,568 @@ use std::sync::{Arc, Mutex};
 pub struct Request {
     pub jsonrpc: String,
     pub method: String,
-    pub params: Value,
-    pub id: i64,
+    #[serde(default)]
+    pub params: Option<Value>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub id: Option<Id>,
 }
 
-pub struct Route {
-    map: HashMap<
-        String,
-        Box<dyn Fn(Arc<DataExtensions>, Request) -> Pin<Box<dyn Future<Output = Value> + Send>>>,
-    >,
-    extensions: Arc<DataExtensions>,
+impl Request {
+    /// 校验请求结构并解析
+    ///   jsonrpc必须为"2.0"，method必须为字符串，params缺省或者为数组、对象
+    ///   校验失败返回Invalid Request错误响应，id尽量取自请求
+    pub fn from_value(req: Value) -> Result<Request, Value> {
+        let invalid_request = |req: &Value| {
+            error_response(
+                Id::of(req).unwrap_or(Id::Null),
+                JsonRpcError::invalid_request(),
+            )
+        };
+
+        let is_valid = match &req {
+            Value::Object(obj) => {
+                obj.get("jsonrpc").and_then(Value::as_str) == Some("2.0")
+                    && obj.get("method").is_some_and(Value::is_string)
+                    && obj
+                        .get("params")
+                        .is_none_or(|params| params.is_array() || params.is_object())
+                    && obj.get("id").is_none_or(|_| Id::of(&req).is_some())
+            }
+            _ => false,
+        };
+        if !is_valid {
+            return Err(invalid_request(&req));
+        }
+
+        serde_json::from_value(req.clone()).map_err(|_| invalid_request(&req))
+    }
+
+    /// 没有id的请求为通知，不需要响应
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+/// 构造成功响应，id原样返回
+pub(crate) fn success_response(id: Id, result: Value) -> Value {
+    json!({"jsonrpc": "2.0", "result": result, "id": id})
 }
 
-unsafe impl Sync for Route {}
+/// 构造错误响应，id原样返回
+pub(crate) fn error_response(id: Id, err: JsonRpcError) -> Value {
+    json!({"jsonrpc": "2.0", "error": err, "id": id})
+}
 
-unsafe impl Send for Route {}
+/// 构造请求消息，params为null时省略，id为None时为通知
+pub fn request_message(method: &str, params: Value, id: Option<&Id>) -> Value {
+    let mut msg = json!({"jsonrpc": "2.0", "method": method});
+    if !params.is_null() {
+        msg["params"] = params;
+    }
+    if let Some(id) = id {
+        msg["id"] = json!(id);
+    }
+    msg
+}
+
+/// 单个请求的执行Future，通知类请求输出None
+pub type ResponseFuture = Pin<Box<dyn Future<Output = Option<Value>> + Send>>;
+
+type Handle = Box<dyn Fn(Arc<DataExtensions>, Context, Request) -> ResponseFuture + Send + Sync>;
+
+struct Endpoint {
+    handle: Handle,
+    // 处理函数依赖的Data类型，在build时校验
+    required_data: Vec<(TypeId, &'static str)>,
+    // 挂载的子Route的共享数据，None时使用所在Route的数据
+    extensions: Option<Arc<DataExtensions>>,
+}
+
+pub struct Route {
+    map: HashMap<String, Endpoint>,
+    extensions: Arc<DataExtensions>,
+    middlewares: Arc<Vec<Box<dyn Middleware>>>,
+    // 重复注册的方法名，在build时报告
+    duplicates: Vec<String>,
+}
 
 impl Route {
     pub fn new() -> Self {
         Route {
             map: HashMap::new(),
             extensions: Arc::new(DataExtensions::default()),
+            middlewares: Arc::new(Vec::new()),
+            duplicates: Vec::new(),
         }
     }
 
-    pub fn to<'a, P, F, R, E, H, T>(mut self, key: String, handle: H) -> Self
+    /// 注册处理函数，处理函数的参数均为提取器，例如 `Data<T>`、`Params<P>`、`Id`
+    ///   处理函数依赖的 `Data<T>` 在 `Route::build` 时校验，与注册顺序无关
+    pub fn to<Args, F, R, E, H>(mut self, key: String, handle: H) -> Self
     where
-        P: for<'de> Deserialize<'de> + Send + 'static,
+        Args: FromRequest + 'static,
         R: Serialize + 'static,
         E: Serialize + Into<JsonRpcError> + 'static,
         F: Future<Output = Result<R, E>> + Send + 'static,
-        H: Fn(Data<T>, P) -> F + 'static + Clone + Send + Sync,
-        T: 'static + Sync + Send,
+        H: Factory<Args, F, Result<R, E>> + Send + Sync,
     {
         let inner_handle = move |extensions: Arc<DataExtensions>,
+                                 context: Context,
                                  req: Request|
-              -> Pin<Box<dyn Future<Output = Value> + Send>> {
-            async fn inner<P, R, E, F, H, T>(
-                extensions: Arc<DataExtensions>,
-                req: Request,
-                handle: H,
-            ) -> Value
+              -> ResponseFuture {
+            async fn inner<Args, F, R, E, H>(req: RequestParts, handle: H) -> Option<Value>
             where
-                P: for<'de> Deserialize<'de> + Send + 'static,
+                Args: FromRequest + 'static,
                 R: Serialize + 'static,
                 E: Serialize + Into<JsonRpcError> + 'static,
                 F: Future<Output = Result<R, E>> + Send + 'static,
-                H: Fn(Data<T>, P) -> F + 'static + Clone + Send + Sync,
-                T: 'static + Sync + Send,
+                H: Factory<Args, F, Result<R, E>> + Send + Sync,
             {
-                let params: P = match serde_json::from_value(req.params) {
-                    Ok(params) => params,
-                    Err(_) => {
-                        return serde_json::to_value(JsonRpc::error(
-                            req.id,
-                            JsonRpcError::invalid_params(),
-                        ))
-                        .unwrap()
+                let args = match Args::from_request(&req) {
+                    Ok(args) => args,
+                    Err(err) => return req.request.id.map(|id| error_response(id, err)),
+                };
+
+                let output = Factory::call(&handle, args).await;
+
+                // 通知类请求执行完毕后丢弃结果
+                let id = req.request.id?;
+                match output {
+                    Ok(result) => Some(success_response(id, serde_json::to_value(result).unwrap())),
+                    Err(err) => Some(error_response(id, err.into())),
+                }
+            }
+
+            let req = RequestParts {
+                request: req,
+                context,
+                extensions,
+                sink: None,
+            };
+            Box::pin(inner(req, handle.clone()))
+        };
+        self.insert(
+            key,
+            Endpoint {
+                handle: Box::new(inner_handle),
+                required_data: Args::required_data(),
+                extensions: None,
+            },
+        );
+        self
+    }
+
+    /// 注册订阅方法，需要传输层在 `Context` 中提供 `Session`
+    ///   调用subscribe方法时创建订阅，处理函数成功返回后响应订阅id
+    ///   处理函数通过 `Sink` 提取器推送notification通知，直到客户端以订阅id调用unsubscribe方法或者连接断开
+    pub fn subscription<Args, F, E, H>(
+        mut self,
+        subscribe: String,
+        notification: String,
+        unsubscribe: String,
+        handle: H,
+    ) -> Self
+    where
+        Args: FromRequest + 'static,
+        E: Serialize + Into<JsonRpcError> + 'static,
+        F: Future<Output = Result<(), E>> + Send + 'static,
+        H: Factory<Args, F, Result<(), E>> + Send + Sync,
+    {
+        let unsubscribe_ = unsubscribe.clone();
+        let inner_handle = move |extensions: Arc<DataExtensions>,
+                                 context: Context,
+                                 req: Request|
+              -> ResponseFuture {
+            async fn inner<Args, F, E, H>(req: RequestParts, handle: H) -> Option<Value>
+            where
+                Args: FromRequest + 'static,
+                E: Serialize + Into<JsonRpcError> + 'static,
+                F: Future<Output = Result<(), E>> + Send + 'static,
+                H: Factory<Args, F, Result<(), E>> + Send + Sync,
+            {
+                let sink = match req.sink.clone() {
+                    Some(sink) => sink,
+                    None => {
+                        return req
+                            .request
+                            .id
+                            .map(|id| error_response(id, subscription_unsupported_error()))
                     }
                 };
 
-                let data_t = extensions.get::<Data<T>>().unwrap().clone();
-                match (handle).call((data_t, params)).await {
-                    Ok(result) => serde_json::to_value(JsonRpc::success(
-                        req.id,
-                        &serde_json::to_value(result).unwrap(),
-                    ))
-                    .unwrap(),
-                    Err(err) => serde_json::to_value(JsonRpc::error(req.id, err.into())).unwrap(),
+                let fut = Args::from_request(&req).map(|args| Factory::call(&handle, args));
+                let output = match fut {
+                    Ok(fut) => fut.await.map_err(Into::into),
+                    Err(err) => Err(err),
+                };
+
+                // 订阅失败时取消订阅
+                if output.is_err() {
+                    sink.cancel();
+                }
+
+                let id = req.request.id?;
+                match output {
+                    Ok(()) => Some(success_response(id, Value::String(sink.id().to_string()))),
+                    Err(err) => Some(error_response(id, err)),
+                }
+            }
+
+            let sink = context
+                .get::<Session>()
+                .map(|session| session.subscribe(&notification, &unsubscribe_));
+            let req = RequestParts {
+                request: req,
+                context,
+                extensions,
+                sink,
+            };
+            Box::pin(inner(req, handle.clone()))
+        };
+        self.insert(
+            subscribe,
+            Endpoint {
+                handle: Box::new(inner_handle),
+                required_data: Args::required_data(),
+                extensions: None,
+            },
+        );
+
+        let unsubscribe_ = unsubscribe.clone();
+        self.to(
+            unsubscribe,
+            move |context: Context, Params(id): Params<String>| {
+                let unsubscribe = unsubscribe_.clone();
+                async move {
+                    match context.get::<Session>() {
+                        Some(session) => Ok(session.unsubscribe(&unsubscribe, &id)),
+                        None => Err(subscription_unsupported_error()),
+                    }
                 }
+            },
+        )
+    }
+
+    /// 挂载子Route，子Route的方法以 `prefix.方法名` 注册，prefix为空时方法名不变
+    ///   子Route的处理函数优先使用子Route注册的 `Data<T>`，`build` 后也可以使用父Route的数据
+    ///   子Route的中间件只作用于子Route的方法，在父Route的中间件之内执行，不能改写方法名以改变路由
+    ///
+    /// ```
+    /// use jsonrpc_core::route::Route;
+    ///
+    /// async fn balance() -> Result<u64, jsonrpc_lite::Error> {
+    ///     Ok(0)
+    /// }
+    ///
+    /// let wallet = Route::new().data(0u64).to("balance".to_string(), balance);
+    /// let route = Route::new().mount("wallet".to_string(), wallet).build().unwrap();
+    /// ```
+    pub fn mount(mut self, prefix: String, scope: Route) -> Self {
+        let Route {
+            map,
+            extensions,
+            middlewares,
+            duplicates,
+        } = scope;
+
+        let prefixed = |method: String| {
+            if prefix.is_empty() {
+                method
+            } else {
+                format!("{}.{}", prefix, method)
             }
-            Box::pin(inner(extensions, req, handle.clone()))
         };
-        self.map.insert(key, Box::new(inner_handle));
+        self.duplicates
+            .extend(duplicates.into_iter().map(&prefixed));
+
+        for (method, endpoint) in map {
+            let handle = if middlewares.is_empty() {
+                endpoint.handle
+            } else {
+                scoped_handle(endpoint.handle, middlewares.clone())
+            };
+            // 更深层子Route的数据以当前子Route为父级
+            let endpoint_extensions = match endpoint.extensions {
+                Some(inner) => {
+                    inner.set_root_parent(&extensions);
+                    inner
+                }
+                None => extensions.clone(),
+            };
+
+            self.insert(
+                prefixed(method),
+                Endpoint {
+                    handle,
+                    required_data: endpoint.required_data,
+                    extensions: Some(endpoint_extensions),
+                },
+            );
+        }
         self
     }
 
-    pub fn data<D: 'static>(mut self, d: D) -> Self {
+    /// 重复的方法名保留先注册的处理函数，build时报告错误
+    fn insert(&mut self, method: String, endpoint: Endpoint) {
+        match self.map.entry(method) {
+            Entry::Occupied(entry) => {
+                log::error!("method {} registered more than once", entry.key());
+                self.duplicates.push(entry.key().clone());
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(endpoint);
+            }
+        }
+    }
+
+    /// 注册共享数据，数据会在多个线程间共享，必须满足 `Send + Sync`
+    ///
+    /// ```compile_fail
+    /// use jsonrpc_core::route::Route;
+    /// use std::rc::Rc;
+    ///
+    /// Route::new().data(Rc::new(0u64));
+    /// ```
+    pub fn data<D: Send + Sync + 'static>(mut self, d: D) -> Self {
         Arc::get_mut(&mut self.extensions)
             .unwrap()
             .insert(Data::new(d));
         self
     }
 
+    /// 注册中间件，多个中间件按注册顺序执行 `on_request`，逆序执行 `on_response`
+    pub fn middleware<M: Middleware>(mut self, middleware: M) -> Self {
+        Arc::get_mut(&mut self.middlewares)
+            .unwrap()
+            .push(Box::new(middleware));
+        self
+    }
+
+    /// 完成构建，校验方法名没有重复注册、所有处理函数依赖的 `Data<T>` 均已注册
+    ///   不满足时返回错误并列出对应的方法及类型，便于在启动阶段发现配置错误
+    ///   build之后不能再调用 `data` 注册数据
+    pub fn build(mut self) -> Result<Self, RouteError> {
+        if !self.duplicates.is_empty() {
+            self.duplicates.sort();
+            self.duplicates.dedup();
+            return Err(RouteError::DuplicateMethod(self.duplicates));
+        }
+
+        // 挂载的子Route找不到的数据到当前Route中查找
+        for endpoint in self.map.values() {
+            if let Some(extensions) = &endpoint.extensions {
+                extensions.set_root_parent(&self.extensions);
+            }
+        }
+
+        let mut missing: Vec<(String, Vec<&'static str>)> = self
+            .map
+            .iter()
+            .filter_map(|(method, endpoint)| {
+                let extensions = endpoint.extensions.as_ref().unwrap_or(&self.extensions);
+                let types: Vec<&'static str> = endpoint
+                    .required_data
+                    .iter()
+                    .filter(|(type_id, _)| !extensions.contains(*type_id))
+                    .map(|(_, name)| *name)
+                    .collect();
+                if types.is_empty() {
+                    None
+                } else {
+                    Some((method.clone(), types))
+                }
+            })
+            .collect();
+
+        if missing.is_empty() {
+            Ok(self)
+        } else {
+            missing.sort();
+            Err(RouteError::MissingData(missing))
+        }
+    }
+
     /// 传入一个Value格式的json-rpc单独请求
     ///   立刻返回响应执行Future或者错误结果
-    pub async fn route_once(
+    ///   通知类请求的Future输出与错误结果均为None，非法请求总是返回错误结果
+    pub async fn route_once(&self, req_str: Value) -> Result<ResponseFuture, Option<Value>> {
+        self.route_once_with_context(req_str, Context::default())
+            .await
+    }
+
+    /// 同 `route_once`，处理函数可以通过 `Context` 提取器取得传入的连接上下文
+    pub async fn route_once_with_context(
         &self,
         req_str: Value,
-    ) -> Result<Pin<Box<dyn Future<Output = Value> + Send>>, Value> {
-        let req: Request = serde_json::from_value(req_str).unwrap();
-        let handle = match self.map.get(&req.method) {
-            Some(handle) => handle,
+        context: Context,
+    ) -> Result<ResponseFuture, Option<Value>> {
+        let mut req = Request::from_value(req_str).map_err(Some)?;
+
+        // 中间件可以修改请求或者直接以错误响应
+        let mut passed = 0;
+        for middleware in self.middlewares.iter() {
+            if let Err(err) = call_on_request(middleware.as_ref(), &mut req, &context) {
+                let resp = req.id.take().map(|id| error_response(id, err));
+                return Err(self.on_response(passed, &req.method, resp));
+            }
+            passed += 1;
+        }
+
+        let endpoint = match self.map.get(&req.method) {
+            Some(endpoint) => endpoint,
             None => {
-                return Err(serde_json::to_value(JsonRpc::error(
-                    req.id,
-                    JsonRpcError::method_not_found(),
-                ))
-                .unwrap())
+                let resp = req
+                    .id
+                    .take()
+                    .map(|id| error_response(id, JsonRpcError::method_not_found()));
+                return Err(self.on_response(passed, &req.method, resp));
             }
         };
 
-        Ok(handle(self.extensions.clone(), req))
+        let id = req.id.clone();
+        let method = req.method.clone();
+        let middlewares = self.middlewares.clone();
+        let extensions = endpoint
+            .extensions
+            .clone()
+            .unwrap_or_else(|| self.extensions.clone());
+        let fut = AssertUnwindSafe((endpoint.handle)(extensions, context, req));
+
+        // 隔离处理函数内部的panic，转为Internal error响应
+        Ok(Box::pin(async move {
+            let output = match fut.catch_unwind().await {
+                Ok(output) => output,
+                Err(panic) => {
+                    log::error!(
+                        "method {} panicked, with info: {}",
+                        method,
+                        panic_message(&panic)
+                    );
+                    id.map(|id| error_response(id, JsonRpcError::internal_error()))
+                }
+            };
+            apply_on_response(&middlewares, middlewares.len(), &method, output)
+        }))
+    }
+
+    fn on_response(&self, passed: usize, method: &str, resp: Option<Value>) -> Option<Value> {
+        apply_on_response(&self.middlewares, passed, method, resp)
+    }
+}
+
+/// 在处理函数前后执行子Route的中间件
+fn scoped_handle(handle: Handle, middlewares: Arc<Vec<Box<dyn Middleware>>>) -> Handle {
+    Box::new(
+        move |extensions: Arc<DataExtensions>, context: Context, mut req: Request| {
+            for (passed, middleware) in middlewares.iter().enumerate() {
+                if let Err(err) = call_on_request(middleware.as_ref(), &mut req, &context) {
+                    let resp = req.id.take().map(|id| error_response(id, err));
+                    let resp = apply_on_response(&middlewares, passed, &req.method, resp);
+                    return Box::pin(async move { resp }) as ResponseFuture;
+                }
+            }
+
+            let method = req.method.clone();
+            let middlewares = middlewares.clone();
+            let fut = handle(extensions, context, req);
+            Box::pin(async move {
+                let output = fut.await;
+                apply_on_response(&middlewares, middlewares.len(), &method, output)
+            })
+        },
+    )
+}
+
+/// 执行中间件的 `on_request`，中间件panic时以Internal error响应
+fn call_on_request(
+    middleware: &dyn Middleware,
+    req: &mut Request,
+    context: &Context,
+) -> Result<(), JsonRpcError> {
+    match panic::catch_unwind(AssertUnwindSafe(|| middleware.on_request(req, context))) {
+        Ok(result) => result,
+        Err(panic) => {
+            log::error!(
+                "middleware panicked on method {}, with info: {}",
+                req.method,
+                panic_message(&panic)
+            );
+            Err(JsonRpcError::internal_error())
+        }
+    }
+}
+
+/// 逆序执行前passed个中间件的 `on_response`，中间件panic时响应替换为Internal error
+fn apply_on_response(
+    middlewares: &[Box<dyn Middleware>],
+    passed: usize,
+    method: &str,
+    mut resp: Option<Value>,
+) -> Option<Value> {
+    if let Some(resp) = resp.as_mut() {
+        for middleware in middlewares[..passed].iter().rev() {
+            if let Err(panic) =
+                panic::catch_unwind(AssertUnwindSafe(|| middleware.on_response(method, resp)))
+            {
+                log::error!(
+                    "middleware panicked on method {} response, with info: {}",
+                    method,
+                    panic_message(&panic)
+                );
+                let id = resp.get_mut("id").map(Value::take).unwrap_or(Value::Null);
+                *resp =
+                    json!({"jsonrpc": "2.0", "error": JsonRpcError::internal_error(), "id": id});
+            }
+        }
+    }
+    resp
+}
+
+fn panic_message(panic: &Box<dyn Any + Send>) -> &str {
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic"
+    }
+}
+
+impl Default for Route {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// 无法解析的请求(如非UTF-8内容)的Parse error响应，id为null
+pub fn parse_error_response() -> String {
+    error_response(Id::Null, JsonRpcError::parse_error()).to_string()
+}
+
 /// 传入jsonrpc请求
-///   返回结果
-pub async fn route_jsonrpc(server: Arc<Route>, req_str: &str) -> String {
+///   返回结果，全部为通知时返回None
+pub async fn route_jsonrpc(server: Arc<Route>, req_str: &str) -> Option<String> {
+    route_jsonrpc_with_context(server, Context::default(), req_str).await
+}
+
+/// 同 `route_jsonrpc`，由传输层传入连接上下文
+pub async fn route_jsonrpc_with_context(
+    server: Arc<Route>,
+    context: Context,
+    req_str: &str,
+) -> Option<String> {
     let req: Value = match serde_json::from_str(req_str) {
         Ok(req) => req,
-        Err(_) => {
-            return serde_json::to_value(JsonRpc::error((), JsonRpcError::parse_error()))
-                .unwrap()
-                .to_string()
-        }
+        Err(_) => return Some(parse_error_response()),
     };
     let resp = match req {
-        Value::Object(_) => match server.route_once(req).await {
+        Value::Object(_) => match server.route_once_with_context(req, context).await {
             Ok(fut) => fut.await,
             Err(err) => err,
         },
+        Value::Array(array) if array.is_empty() => {
+            Some(error_response(Id::Null, JsonRpcError::invalid_request()))
+        }
         Value::Array(array) => {
             let share_outputs = Arc::new(Mutex::new(Vec::<Value>::new()));
             let mut tasks = Vec::new();
@@ -143,24 +594,27 @@ pub async fn route_jsonrpc(server: Arc<Route>, req_str: &str) -> String {
             for each in array {
                 let inner_server = Arc::downgrade(&server);
                 let share_outputs = share_outputs.clone();
+                let context = context.clone();
 
                 tasks.push(async move {
                     // task开始执行是尝试获取server对象
                     let output = match inner_server.upgrade() {
-                        Some(server) => match server.route_once(each).await {
+                        Some(server) => match server.route_once_with_context(each, context).await {
                             Ok(fut) => fut.await,
                             Err(err) => err,
                         },
-                        None => serde_json::to_value(server_route_error()).unwrap(),
+                        None => Id::of(&each).map(|id| error_response(id, server_route_error())),
                     };
 
-                    let mut outputs = share_outputs.lock().unwrap();
-                    outputs.push(output);
+                    // 通知类请求不放入批量响应
+                    if let Some(output) = output {
+                        let mut outputs = share_outputs.lock().unwrap();
+                        outputs.push(output);
+                    }
                 });
             }
             join_all(tasks).await;
 
-            // TODO 内部panic可能要处理
             // outputs Arc持有者只剩下一个，此处取出不会失败，也不考虑失败处理
             let output = if let Ok(outputs) = Arc::try_unwrap(share_outputs) {
                 // 锁持有者同理
@@ -168,14 +622,15 @@ pub async fn route_jsonrpc(server: Arc<Route>, req_str: &str) -> String {
             } else {
                 panic!("Arc<Mutex<>> into_inner failed");
             };
-            Value::Array(output)
-        }
-        _ => {
-            return serde_json::to_value(JsonRpc::error((), JsonRpcError::parse_error()))
-                .unwrap()
-                .to_string()
+
+            if output.is_empty() {
+                None
+            } else {
+                Some(Value::Array(output))
+            }
         }
+        _ => Some(error_response(Id::Null, JsonRpcError::invalid_request())),
     };
 
-    resp.to_string()
+    resp.map(|resp| resp.to_string())
 }
@@ -0,0 +1,291 @@
+use crate::id::Id;
+use crate::route::request_message;
+use futures_channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures_channel::oneshot;
+use futures_timer::Delay;
+use futures_util::future::{self, Either};
+use fxhash::FxHashMap;
+use jsonrpc_lite::Error as JsonRpcError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 连接级别的会话，由传输层创建并放入 `Context`
+///   用于向对端推送通知以及管理该连接上的订阅
+#[derive(Clone)]
+pub struct Session(Arc<SessionInner>);
+
+struct SessionInner {
+    sender: UnboundedSender<String>,
+    next_id: AtomicU64,
+    // 订阅id -> (取消订阅的方法名, 订阅是否有效)
+    subscriptions: Mutex<FxHashMap<String, (String, Arc<AtomicBool>)>>,
+    // 向对端发出的请求id -> 等待响应的通道
+    pending: Mutex<FxHashMap<Id, oneshot::Sender<Result<Value, JsonRpcError>>>>,
+    request_timeout: Duration,
+}
+
+/// 会话已关闭或订阅已取消
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionClosed;
+
+/// 向对端发出请求的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallError {
+    /// 对端返回的错误响应
+    Rpc(JsonRpcError),
+    /// 超时未收到响应
+    Timeout,
+    /// 会话已关闭
+    Closed,
+    /// 参数或结果格式错误
+    Parse(String),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::Rpc(err) => write!(f, "peer error {}: {}", err.code, err.message),
+            CallError::Timeout => write!(f, "peer request timeout"),
+            CallError::Closed => write!(f, "session closed"),
+            CallError::Parse(err) => write!(f, "parse error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+#[derive(Deserialize)]
+struct Response {
+    id: Id,
+    result: Option<Value>,
+    error: Option<JsonRpcError>,
+}
+
+impl Session {
+    /// 创建会话，传输层需要将返回的Receiver中的消息写回对端
+    pub fn new() -> (Self, UnboundedReceiver<String>) {
+        Self::with_request_timeout(REQUEST_TIMEOUT)
+    }
+
+    /// 创建会话，并指定向对端发出请求的超时时间
+    pub fn with_request_timeout(request_timeout: Duration) -> (Self, UnboundedReceiver<String>) {
+        let (sender, receiver) = mpsc::unbounded();
+        let inner = SessionInner {
+            sender,
+            next_id: AtomicU64::new(1),
+            subscriptions: Mutex::new(FxHashMap::default()),
+            pending: Mutex::new(FxHashMap::default()),
+            request_timeout,
+        };
+        (Session(Arc::new(inner)), receiver)
+    }
+
+    /// 向对端发出请求并等待响应
+    ///   超时、会话关闭或者对端返回错误时返回对应的CallError
+    pub fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> impl Future<Output = Result<R, CallError>> {
+        let session = self.clone();
+        let id = Id::from(self.0.next_id.fetch_add(1, Ordering::SeqCst));
+        let req =
+            serde_json::to_value(params).map(|params| request_message(method, params, Some(&id)));
+
+        async move {
+            let req = req.map_err(|err| CallError::Parse(err.to_string()))?;
+
+            let (resp_in, resp_out) = oneshot::channel();
+            session
+                .0
+                .pending
+                .lock()
+                .unwrap()
+                .insert(id.clone(), resp_in);
+
+            if session.send(req.to_string()).is_err() {
+                session.0.pending.lock().unwrap().remove(&id);
+                return Err(CallError::Closed);
+            }
+
+            let timeout = Delay::new(session.0.request_timeout);
+            let output = match future::select(resp_out, timeout).await {
+                Either::Left((Ok(output), _)) => output,
+                Either::Left((Err(_), _)) => return Err(CallError::Closed),
+                Either::Right(_) => {
+                    session.0.pending.lock().unwrap().remove(&id);
+                    return Err(CallError::Timeout);
+                }
+            };
+
+            let result = output.map_err(CallError::Rpc)?;
+            serde_json::from_value(result).map_err(|err| CallError::Parse(err.to_string()))
+        }
+    }
+
+    /// 处理对端发来的消息，如果是对本端请求的响应(或批量响应)则交给等待者并返回true
+    ///   返回false表示该消息需要作为请求处理
+    ///   无对应请求的响应(如等待者已超时)记录日志后丢弃，不会作为请求处理
+    pub fn handle_response(&self, msg: &str) -> bool {
+        let value: Value = match serde_json::from_str(msg) {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+
+        let responses = match value {
+            Value::Array(array) if !array.is_empty() && array.iter().all(is_response) => array,
+            value if is_response(&value) => vec![value],
+            _ => return false,
+        };
+
+        for resp in responses {
+            let resp: Response = match serde_json::from_value(resp) {
+                Ok(resp) => resp,
+                Err(err) => {
+                    log::warn!("peer response format error, with info: {}", err);
+                    continue;
+                }
+            };
+
+            let output = match resp.error {
+                Some(err) => Err(err),
+                None => Ok(resp.result.unwrap_or(Value::Null)),
+            };
+
+            match self.0.pending.lock().unwrap().remove(&resp.id) {
+                Some(resp_in) => {
+                    // 等待者已超时，忽略
+                    let _ = resp_in.send(output);
+                }
+                None => log::warn!("peer response {} has no pending request, ignore", resp.id),
+            }
+        }
+        true
+    }
+
+    /// 向对端发送通知，params为 `()` 时省略params
+    pub fn notify<P: Serialize>(&self, method: &str, params: P) -> Result<(), SessionClosed> {
+        let params = serde_json::to_value(params).map_err(|_| SessionClosed)?;
+        self.send(request_message(method, params, None).to_string())
+    }
+
+    /// 向对端发送原始消息
+    pub fn send(&self, msg: String) -> Result<(), SessionClosed> {
+        self.0.sender.unbounded_send(msg).map_err(|_| SessionClosed)
+    }
+
+    /// 关闭会话，取消所有订阅及等待中的请求，连接断开时由传输层调用
+    pub fn close(&self) {
+        let mut subscriptions = self.0.subscriptions.lock().unwrap();
+        for (_, (_, active)) in subscriptions.drain() {
+            active.store(false, Ordering::SeqCst);
+        }
+        self.0.pending.lock().unwrap().clear();
+        self.0.sender.close_channel();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.0.sender.is_closed()
+    }
+
+    pub(crate) fn subscribe(&self, notification: &str, unsubscribe: &str) -> Sink {
+        let id = format!("{:#x}", self.0.next_id.fetch_add(1, Ordering::SeqCst));
+        let active = Arc::new(AtomicBool::new(true));
+
+        self.0
+            .subscriptions
+            .lock()
+            .unwrap()
+            .insert(id.clone(), (unsubscribe.to_string(), active.clone()));
+
+        Sink {
+            id,
+            notification: notification.to_string(),
+            session: self.clone(),
+            active,
+        }
+    }
+
+    /// 取消订阅，只有与订阅对应的取消方法才能取消
+    pub(crate) fn unsubscribe(&self, unsubscribe: &str, id: &str) -> bool {
+        let mut subscriptions = self.0.subscriptions.lock().unwrap();
+        match subscriptions.get(id) {
+            Some((method, _)) if method == unsubscribe => {
+                let (_, active) = subscriptions.remove(id).unwrap();
+                active.store(false, Ordering::SeqCst);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 订阅的推送端，处理函数通过提取器取得
+///   推送的通知格式为 `{"method": notification, "params": {"subscription": id, "result": ...}}`
+#[derive(Clone)]
+pub struct Sink {
+    id: String,
+    notification: String,
+    session: Session,
+    active: Arc<AtomicBool>,
+}
+
+impl Sink {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 推送一条订阅结果，订阅已取消或连接已断开时返回错误
+    pub fn notify<T: Serialize>(&self, result: T) -> Result<(), SessionClosed> {
+        if self.is_closed() {
+            return Err(SessionClosed);
+        }
+
+        let result = serde_json::to_value(result).map_err(|_| SessionClosed)?;
+        self.session.notify(
+            &self.notification,
+            json!({"subscription": self.id, "result": result}),
+        )
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.active.load(Ordering::SeqCst) || self.session.is_closed()
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.active.store(false, Ordering::SeqCst);
+        self.session
+            .0
+            .subscriptions
+            .lock()
+            .unwrap()
+            .remove(&self.id);
+    }
+}
+
+pub(crate) fn subscription_unsupported_error() -> JsonRpcError {
+    JsonRpcError {
+        code: -32000,
+        message: "Subscription is not supported on this transport".to_string(),
+        data: None,
+    }
+}
+
+fn is_response(value: &Value) -> bool {
+    match value {
+        Value::Object(obj) => {
+            obj.get("method").is_none()
+                && obj.contains_key("id")
+                && (obj.contains_key("result") || obj.contains_key("error"))
+        }
+        _ => false,
+    }
+}
@@ -1,6 +1,9 @@
-use jsonrpc_core::route::route_jsonrpc;
+use futures_util::StreamExt;
 use jsonrpc_core::route::Route;
-use jsonrpc_core::Data;
+use jsonrpc_core::route::{route_jsonrpc, route_jsonrpc_with_context, Request};
+use jsonrpc_core::{
+    CallError, Context, Data, Id, Method, Middleware, Params, RouteError, Session, Sink,
+};
 use jsonrpc_lite::Error as JsonRpcError;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -36,8 +39,8 @@ pub enum TestError {
     WebSockServerGetPeerError,
 }
 
-impl Into<JsonRpcError> for TestError {
-    fn into(self) -> JsonRpcError {
+impl From<TestError> for JsonRpcError {
+    fn from(_: TestError) -> JsonRpcError {
         JsonRpcError {
             code: 1000i64,
             message: "test".to_string(),
@@ -46,7 +49,10 @@ impl Into<JsonRpcError> for TestError {
     }
 }
 
-async fn route_b(local_test: Data<ShareStateTest>, req: ReqTest) -> Result<RespTest, TestError> {
+async fn route_b(
+    local_test: Data<ShareStateTest>,
+    Params(req): Params<ReqTest>,
+) -> Result<RespTest, TestError> {
     time_sleep(1000).await;
 
     let mut a = local_test.get_ref().a.lock().unwrap();
@@ -64,7 +70,7 @@ async fn route_b(local_test: Data<ShareStateTest>, req: ReqTest) -> Result<RespT
 }
 
 async fn time_sleep(timeout_ms: u64) {
-    time::delay_for(Duration::from_millis(timeout_ms.into())).await;
+    time::delay_for(Duration::from_millis(timeout_ms)).await;
 }
 
 #[test]
@@ -97,7 +103,7 @@ fn test_server_simple() {
                 "id":99,
                 "jsonrpc":"2.0"
             }),
-            resp.unwrap().await
+            resp.unwrap().await.unwrap()
         );
     });
 }
@@ -125,7 +131,8 @@ async fn test_server_route_and_array() {
                 })
                 .to_string(),
             )
-            .await,
+            .await
+            .unwrap(),
         )
         .unwrap();
 
@@ -156,7 +163,8 @@ async fn test_server_route_and_array() {
                 }])
                 .to_string(),
             )
-            .await,
+            .await
+            .unwrap(),
         )
         .unwrap();
 
@@ -192,3 +200,1227 @@ async fn test_server_route_and_array() {
 
     tokio::spawn(tasks).await.unwrap();
 }
+
+#[tokio::test]
+async fn test_server_notification() {
+    let route = Arc::new(
+        Route::new()
+            .data(ShareStateTest {
+                a: Mutex::new(100u64),
+                b: Mutex::new("abcdefg".to_string()),
+            })
+            .to("route_b".to_string(), route_b),
+    );
+
+    let resp = route_jsonrpc(
+        route.clone(),
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "route_b",
+            "params": {"a": 1u64, "b":"_1_", "c":[]},
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(None, resp);
+
+    // 通知仍然会被执行
+    let state = route
+        .route_once(json!({
+            "jsonrpc": "2.0",
+            "method": "route_b",
+            "params": {"a": 0u64, "b":"_0_", "c":[]},
+            "id": 1,
+        }))
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(102, state["result"]["a"].as_u64().unwrap());
+
+    let resp = route_jsonrpc(
+        route.clone(),
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "not_exist",
+            "params": {},
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(None, resp);
+
+    let resp = route_jsonrpc(
+        route.clone(),
+        &json!([{
+            "jsonrpc": "2.0",
+            "method": "route_b",
+            "params": {"a": 1u64, "b":"_1_", "c":[]},
+        },{
+            "jsonrpc": "2.0",
+            "method": "not_exist",
+            "params": {},
+        }])
+        .to_string(),
+    )
+    .await;
+    assert_eq!(None, resp);
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!([{
+                "jsonrpc": "2.0",
+                "method": "route_b",
+                "params": {"a": 1u64, "b":"_1_", "c":[]},
+            },{
+                "jsonrpc": "2.0",
+                "method": "route_b",
+                "params": {"err_param": 1},
+                "id": 95,
+            }])
+            .to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        json!([{"error":{"code":-32602,"message":"Invalid params"},"id":95,"jsonrpc":"2.0"}]),
+        resp
+    );
+}
+
+#[tokio::test]
+async fn test_server_id_verbatim() {
+    let route = Arc::new(
+        Route::new()
+            .data(ShareStateTest {
+                a: Mutex::new(100u64),
+                b: Mutex::new("abcdefg".to_string()),
+            })
+            .to("route_b".to_string(), route_b),
+    );
+
+    for id in &[
+        r#""3b241101-e2bb-4255-8caf-4136c566a962""#,
+        "null",
+        "123456789012345678901234567890",
+        "1.50",
+        "-1",
+    ] {
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","method":"route_b","params":{{"err_param":1}},"id":{}}}"#,
+            id
+        );
+        let resp = route_jsonrpc(route.clone(), &req).await.unwrap();
+        assert!(resp.contains(&format!(r#""id":{},"#, id)), "{}", resp);
+
+        let req = format!(
+            r#"[{{"jsonrpc":"2.0","method":"not_exist","params":{{}},"id":{}}}]"#,
+            id
+        );
+        let resp = route_jsonrpc(route.clone(), &req).await.unwrap();
+        assert!(resp.contains(&format!(r#""id":{},"#, id)), "{}", resp);
+    }
+}
+
+#[tokio::test]
+async fn test_server_invalid_request() {
+    let route = Arc::new(
+        Route::new()
+            .data(ShareStateTest {
+                a: Mutex::new(100u64),
+                b: Mutex::new("abcdefg".to_string()),
+            })
+            .to("route_b".to_string(), route_b),
+    );
+
+    let invalid_request = |id: Value| json!({"error":{"code":-32600,"message":"Invalid request"},"id":id,"jsonrpc":"2.0"});
+
+    for (req, id) in vec![
+        (json!({"jsonrpc": "2.0", "params": {}, "id": 1}), json!(1)),
+        (
+            json!({"jsonrpc": 2.0, "method": "route_b", "id": "a"}),
+            json!("a"),
+        ),
+        (
+            json!({"jsonrpc": "1.0", "method": "route_b", "id": 2}),
+            json!(2),
+        ),
+        (json!({"method": "route_b", "id": 3}), json!(3)),
+        (json!({"jsonrpc": "2.0", "method": 1, "id": 4}), json!(4)),
+        (
+            json!({"jsonrpc": "2.0", "method": "route_b", "params": "x", "id": 5}),
+            json!(5),
+        ),
+        (
+            json!({"jsonrpc": "2.0", "method": "route_b", "params": null}),
+            Value::Null,
+        ),
+        (
+            json!({"jsonrpc": "2.0", "method": "route_b", "id": {"a": 1}}),
+            Value::Null,
+        ),
+        (json!([]), Value::Null),
+        (json!(1), Value::Null),
+        (json!("route_b"), Value::Null),
+    ] {
+        let resp: Value = serde_json::from_str(
+            &route_jsonrpc(route.clone(), &req.to_string())
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(invalid_request(id), resp, "{}", req);
+    }
+
+    let resp = route
+        .route_once(json!({"jsonrpc": "2.0", "params": {}}))
+        .await;
+    assert_eq!(Some(invalid_request(Value::Null)), resp.err().unwrap());
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(route.clone(), &json!([1, 2]).to_string())
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        json!([invalid_request(Value::Null), invalid_request(Value::Null)]),
+        resp
+    );
+}
+
+#[tokio::test]
+async fn test_server_invalid_request_in_array() {
+    let route = Arc::new(
+        Route::new()
+            .data(ShareStateTest {
+                a: Mutex::new(100u64),
+                b: Mutex::new("abcdefg".to_string()),
+            })
+            .to("route_b".to_string(), route_b),
+    );
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!([{
+                "jsonrpc": "2.0",
+                "params": {"a": 1u64, "b":"_1_", "c":[]},
+                "id": 81,
+            },
+            "bad element",
+            {
+                "jsonrpc": "2.0",
+                "method": "route_b",
+                "params": {"a": 1u64, "b":"_1_", "c":[]},
+                "id": 82,
+            }])
+            .to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+
+    let resp_vec = resp.as_array().unwrap();
+    assert_eq!(3, resp_vec.len());
+
+    let ans_81: Vec<&Value> = resp_vec.iter().filter(|&resp| resp["id"] == 81).collect();
+    assert_eq!(1, ans_81.len());
+    assert_eq!(-32600, ans_81[0]["error"]["code"].as_i64().unwrap());
+
+    let ans_null: Vec<&Value> = resp_vec
+        .iter()
+        .filter(|&resp| resp["id"].is_null())
+        .collect();
+    assert_eq!(1, ans_null.len());
+    assert_eq!(-32600, ans_null[0]["error"]["code"].as_i64().unwrap());
+
+    let ans_82: Vec<&Value> = resp_vec.iter().filter(|&resp| resp["id"] == 82).collect();
+    assert_eq!(1, ans_82.len());
+    assert_eq!(102, ans_82[0]["result"]["a"].as_u64().unwrap());
+}
+
+async fn route_panic(
+    _local_test: Data<ShareStateTest>,
+    Params(req): Params<ReqTest>,
+) -> Result<RespTest, TestError> {
+    time_sleep(100).await;
+    panic!("route_panic with a: {}", req.a);
+}
+
+#[tokio::test]
+async fn test_server_handle_panic() {
+    let route = Arc::new(
+        Route::new()
+            .data(ShareStateTest {
+                a: Mutex::new(100u64),
+                b: Mutex::new("abcdefg".to_string()),
+            })
+            .to("route_b".to_string(), route_b)
+            .to("route_panic".to_string(), route_panic),
+    );
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "route_panic",
+                "params": {"a": 1u64, "b":"_1_", "c":[]},
+                "id": 71,
+            })
+            .to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        json!({"error":{"code":-32603,"message":"Internal error"},"id":71,"jsonrpc":"2.0"}),
+        resp
+    );
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!([{
+                "jsonrpc": "2.0",
+                "method": "route_panic",
+                "params": {"a": 1u64, "b":"_1_", "c":[]},
+                "id": 72,
+            },{
+                "jsonrpc": "2.0",
+                "method": "route_panic",
+                "params": {"a": 1u64, "b":"_1_", "c":[]},
+            },{
+                "jsonrpc": "2.0",
+                "method": "route_b",
+                "params": {"a": 1u64, "b":"_1_", "c":[]},
+                "id": 73,
+            }])
+            .to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+
+    let resp_vec = resp.as_array().unwrap();
+    assert_eq!(2, resp_vec.len());
+
+    let ans_72: Vec<&Value> = resp_vec.iter().filter(|&resp| resp["id"] == 72).collect();
+    assert_eq!(1, ans_72.len());
+    assert_eq!(-32603, ans_72[0]["error"]["code"].as_i64().unwrap());
+
+    let ans_73: Vec<&Value> = resp_vec.iter().filter(|&resp| resp["id"] == 73).collect();
+    assert_eq!(1, ans_73.len());
+    assert_eq!(102, ans_73[0]["result"]["a"].as_u64().unwrap());
+}
+
+#[tokio::test]
+async fn test_server_missing_data_without_build() {
+    let route = Arc::new(Route::new().to("route_b".to_string(), route_b));
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "route_b",
+                "params": {"a": 1u64, "b":"_1_", "c":[]},
+                "id": 74,
+            })
+            .to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(-32603, resp["error"]["code"].as_i64().unwrap());
+}
+
+#[test]
+fn test_server_missing_data() {
+    let err = Route::new()
+        .to("route_b".to_string(), route_b)
+        .to("route_ping".to_string(), route_ping)
+        .build()
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        RouteError::MissingData(vec![
+            ("route_b".to_string(), vec!["test::ShareStateTest"]),
+            ("route_ping".to_string(), vec!["test::ShareStateTest"]),
+        ]),
+        err
+    );
+    assert_eq!(
+        "method route_b require data [test::ShareStateTest]; \
+         method route_ping require data [test::ShareStateTest], register it by Route::data",
+        err.to_string()
+    );
+}
+
+async fn route_ping(
+    local_test: Data<ShareStateTest>,
+    Params(()): Params<()>,
+) -> Result<String, TestError> {
+    Ok(local_test.get_ref().b.lock().unwrap().clone())
+}
+
+#[tokio::test]
+async fn test_server_positional_and_omitted_params() {
+    let route = Arc::new(
+        Route::new()
+            .data(ShareStateTest {
+                a: Mutex::new(100u64),
+                b: Mutex::new("abcdefg".to_string()),
+            })
+            .to("route_b".to_string(), route_b)
+            .to("route_ping".to_string(), route_ping),
+    );
+
+    for req in [
+        json!({"jsonrpc": "2.0", "method": "route_ping", "id": 1}),
+        json!({"jsonrpc": "2.0", "method": "route_ping", "params": [], "id": 1}),
+        json!({"jsonrpc": "2.0", "method": "route_ping", "params": {}, "id": 1}),
+    ] {
+        let resp: Value = serde_json::from_str(
+            &route_jsonrpc(route.clone(), &req.to_string())
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            json!({"jsonrpc": "2.0", "result": "abcdefg", "id": 1}),
+            resp
+        );
+    }
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "route_b",
+                "params": [8888u64, "_8888_", ["c"]],
+                "id": 2,
+            })
+            .to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    let result = serde_json::from_value::<RespTest>(resp["result"].clone()).unwrap();
+    assert_eq!(8888u64 + 101, result.a);
+    assert_eq!("_8888_", result.b);
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({"jsonrpc": "2.0", "method": "route_b", "id": 3}).to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(-32602, resp["error"]["code"].as_i64().unwrap());
+}
+
+async fn route_stateless() -> Result<u64, TestError> {
+    Ok(7)
+}
+
+async fn route_echo(
+    id: Id,
+    Method(method): Method,
+    context: Context,
+    Params((a, b)): Params<(u64, String)>,
+) -> Result<Value, TestError> {
+    Ok(json!({
+        "id": id,
+        "method": method,
+        "peer": context.get::<String>(),
+        "a": a,
+        "b": b,
+    }))
+}
+
+async fn route_count(id: Option<Id>, local_test: Data<ShareStateTest>) -> Result<u64, TestError> {
+    let mut a = local_test.get_ref().a.lock().unwrap();
+    if id.is_none() {
+        *a += 1;
+    }
+    Ok(*a)
+}
+
+#[tokio::test]
+async fn test_server_extractor() {
+    let route = Arc::new(
+        Route::new()
+            .data(ShareStateTest {
+                a: Mutex::new(100u64),
+                b: Mutex::new("abcdefg".to_string()),
+            })
+            .to("route_stateless".to_string(), route_stateless)
+            .to("route_echo".to_string(), route_echo)
+            .to("route_count".to_string(), route_count),
+    );
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({"jsonrpc": "2.0", "method": "route_stateless", "id": 1}).to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(json!({"jsonrpc": "2.0", "result": 7, "id": 1}), resp);
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc_with_context(
+            route.clone(),
+            Context::new().with("127.0.0.1:9000".to_string()),
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "route_echo",
+                "params": [1, "x"],
+                "id": "abc",
+            })
+            .to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        json!({
+            "id": "abc",
+            "method": "route_echo",
+            "peer": "127.0.0.1:9000",
+            "a": 1,
+            "b": "x",
+        }),
+        resp["result"]
+    );
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({"jsonrpc": "2.0", "method": "route_echo", "params": [1], "id": 2}).to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(-32602, resp["error"]["code"].as_i64().unwrap());
+
+    let resp = route_jsonrpc(
+        route.clone(),
+        &json!({"jsonrpc": "2.0", "method": "route_count"}).to_string(),
+    )
+    .await;
+    assert_eq!(None, resp);
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({"jsonrpc": "2.0", "method": "route_count", "id": 3}).to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(101, resp["result"].as_u64().unwrap());
+}
+
+pub struct ConfigTest {
+    pub step: u64,
+}
+
+async fn route_multi_data(
+    local_test: Data<ShareStateTest>,
+    config: Data<ConfigTest>,
+    name: Data<String>,
+) -> Result<String, TestError> {
+    let mut a = local_test.get_ref().a.lock().unwrap();
+    *a += config.get_ref().step;
+    Ok(format!("{}:{}", name.get_ref(), *a))
+}
+
+#[tokio::test]
+async fn test_server_multi_data() {
+    let route = Arc::new(
+        Route::new()
+            .data(ShareStateTest {
+                a: Mutex::new(100u64),
+                b: Mutex::new("abcdefg".to_string()),
+            })
+            .to("route_multi_data".to_string(), route_multi_data)
+            .data(ConfigTest { step: 10 })
+            .data("multi".to_string())
+            .build()
+            .unwrap(),
+    );
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({"jsonrpc": "2.0", "method": "route_multi_data", "id": 1}).to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": "multi:110", "id": 1}),
+        resp
+    );
+}
+
+#[test]
+fn test_server_multi_data_missing() {
+    let err = Route::new()
+        .data(ShareStateTest {
+            a: Mutex::new(100u64),
+            b: Mutex::new("abcdefg".to_string()),
+        })
+        .to("route_multi_data".to_string(), route_multi_data)
+        .build()
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        RouteError::MissingData(vec![(
+            "route_multi_data".to_string(),
+            vec!["test::ConfigTest", "alloc::string::String"]
+        )]),
+        err
+    );
+}
+
+#[test]
+fn test_server_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Route>();
+    assert_send_sync::<Context>();
+    assert_send_sync::<Data<ShareStateTest>>();
+}
+
+async fn route_ticks(sink: Sink, Params(count): Params<u64>) -> Result<(), TestError> {
+    if count == 0 {
+        return Err(TestError::WebSockServerBindError);
+    }
+
+    tokio::spawn(async move {
+        for n in 0..count {
+            time_sleep(10).await;
+            if sink.notify(n).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+fn subscription_route() -> Arc<Route> {
+    Arc::new(
+        Route::new()
+            .subscription(
+                "subscribe_ticks".to_string(),
+                "ticks".to_string(),
+                "unsubscribe_ticks".to_string(),
+                route_ticks,
+            )
+            .subscription(
+                "subscribe_other".to_string(),
+                "other".to_string(),
+                "unsubscribe_other".to_string(),
+                route_ticks,
+            )
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn call_with_context(route: &Arc<Route>, context: &Context, req: Value) -> Value {
+    serde_json::from_str(
+        &route_jsonrpc_with_context(route.clone(), context.clone(), &req.to_string())
+            .await
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_server_subscription() {
+    let route = subscription_route();
+    let (session, mut push_pipe) = Session::new();
+    let context = Context::new().with(session.clone());
+
+    let resp = call_with_context(
+        &route,
+        &context,
+        json!({"jsonrpc": "2.0", "method": "subscribe_ticks", "params": [3], "id": 1}),
+    )
+    .await;
+    assert_eq!(json!({"jsonrpc": "2.0", "result": "0x1", "id": 1}), resp);
+
+    for n in 0..3 {
+        let notification: Value = serde_json::from_str(&push_pipe.next().await.unwrap()).unwrap();
+        assert_eq!(
+            json!({
+                "jsonrpc": "2.0",
+                "method": "ticks",
+                "params": {"subscription": "0x1", "result": n},
+            }),
+            notification
+        );
+    }
+
+    // 取消方法与订阅不匹配
+    let resp = call_with_context(
+        &route,
+        &context,
+        json!({"jsonrpc": "2.0", "method": "unsubscribe_other", "params": ["0x1"], "id": 2}),
+    )
+    .await;
+    assert_eq!(false, resp["result"]);
+
+    let resp = call_with_context(
+        &route,
+        &context,
+        json!({"jsonrpc": "2.0", "method": "unsubscribe_ticks", "params": ["0x1"], "id": 3}),
+    )
+    .await;
+    assert_eq!(true, resp["result"]);
+
+    let resp = call_with_context(
+        &route,
+        &context,
+        json!({"jsonrpc": "2.0", "method": "unsubscribe_ticks", "params": ["0x1"], "id": 4}),
+    )
+    .await;
+    assert_eq!(false, resp["result"]);
+
+    // 处理函数失败时不创建订阅
+    let resp = call_with_context(
+        &route,
+        &context,
+        json!({"jsonrpc": "2.0", "method": "subscribe_ticks", "params": [0], "id": 5}),
+    )
+    .await;
+    assert_eq!(1000, resp["error"]["code"]);
+
+    let resp = call_with_context(
+        &route,
+        &context,
+        json!({"jsonrpc": "2.0", "method": "unsubscribe_ticks", "params": ["0x2"], "id": 6}),
+    )
+    .await;
+    assert_eq!(false, resp["result"]);
+}
+
+#[tokio::test]
+async fn test_server_subscription_close() {
+    let route = subscription_route();
+    let (session, mut push_pipe) = Session::new();
+    let context = Context::new().with(session.clone());
+
+    let resp = call_with_context(
+        &route,
+        &context,
+        json!({"jsonrpc": "2.0", "method": "subscribe_ticks", "params": [1000], "id": 1}),
+    )
+    .await;
+    let sub_id = resp["result"].clone();
+
+    let notification: Value = serde_json::from_str(&push_pipe.next().await.unwrap()).unwrap();
+    assert_eq!(sub_id, notification["params"]["subscription"]);
+
+    // 连接断开后推送结束
+    session.close();
+    assert!(session.is_closed());
+    while push_pipe.next().await.is_some() {}
+
+    let resp = call_with_context(
+        &route,
+        &context,
+        json!({"jsonrpc": "2.0", "method": "unsubscribe_ticks", "params": [sub_id], "id": 2}),
+    )
+    .await;
+    assert_eq!(false, resp["result"]);
+}
+
+#[tokio::test]
+async fn test_server_subscription_unsupported() {
+    let route = subscription_route();
+
+    let resp: Value = serde_json::from_str(
+        &route_jsonrpc(
+            route.clone(),
+            &json!({"jsonrpc": "2.0", "method": "subscribe_ticks", "params": [3], "id": 1})
+                .to_string(),
+        )
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(-32000, resp["error"]["code"]);
+}
+
+async fn route_confirm(
+    session: Session,
+    Params(msg): Params<String>,
+) -> Result<bool, JsonRpcError> {
+    session
+        .request("confirm", vec![msg])
+        .await
+        .map_err(|_| JsonRpcError::internal_error())
+}
+
+#[tokio::test]
+async fn test_server_request() {
+    let route = Arc::new(
+        Route::new()
+            .to("route_confirm".to_string(), route_confirm)
+            .build()
+            .unwrap(),
+    );
+    let (session, mut push_pipe) = Session::new();
+    let context = Context::new().with(session.clone());
+
+    // 没有等待中的请求时，响应也不会交给Route处理
+    assert!(session.handle_response(r#"{"jsonrpc": "2.0", "result": true, "id": 1}"#));
+
+    let call = tokio::spawn(async move {
+        call_with_context(
+            &route,
+            &context,
+            json!({"jsonrpc": "2.0", "method": "route_confirm", "params": ["ok?"], "id": "a"}),
+        )
+        .await
+    });
+
+    // 模拟客户端收到服务端请求并应答
+    let req: Value = serde_json::from_str(&push_pipe.next().await.unwrap()).unwrap();
+    assert_eq!("confirm", req["method"]);
+    assert_eq!(json!(["ok?"]), req["params"]);
+    assert!(!session.handle_response(&req.to_string()));
+    assert!(session
+        .handle_response(&json!({"jsonrpc": "2.0", "result": true, "id": req["id"]}).to_string()));
+
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": true, "id": "a"}),
+        call.await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_server_request_error() {
+    let (session, mut push_pipe) = Session::with_request_timeout(Duration::from_millis(100));
+
+    let call = tokio::spawn(session.request::<_, bool>("confirm", ()));
+    let req: Value = serde_json::from_str(&push_pipe.next().await.unwrap()).unwrap();
+    assert_eq!(None, req.get("params"));
+    session.handle_response(
+        &json!([{"jsonrpc": "2.0", "error": {"code": -1, "message": "no"}, "id": req["id"]}])
+            .to_string(),
+    );
+    match call.await.unwrap() {
+        Err(CallError::Rpc(err)) => assert_eq!(-1, err.code),
+        _ => panic!("expect rpc error"),
+    }
+
+    // 客户端不应答时超时
+    let result = session.request::<_, bool>("confirm", ()).await;
+    assert_eq!(Err(CallError::Timeout), result);
+    let req: Value = serde_json::from_str(&push_pipe.next().await.unwrap()).unwrap();
+
+    // 超时后到达的响应被丢弃，不会作为请求处理
+    assert!(session
+        .handle_response(&json!({"jsonrpc": "2.0", "result": true, "id": req["id"]}).to_string()));
+
+    // 会话关闭后等待中的请求结束
+    let call = tokio::spawn(session.request::<_, bool>("confirm", ()));
+    push_pipe.next().await.unwrap();
+    session.close();
+    assert_eq!(Err(CallError::Closed), call.await.unwrap());
+    assert_eq!(
+        Err(CallError::Closed),
+        session.request::<_, bool>("confirm", ()).await
+    );
+}
+
+async fn route_identity(Params(params): Params<Value>) -> Result<Value, TestError> {
+    Ok(params)
+}
+
+struct Recorder {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl Middleware for Recorder {
+    fn on_request(&self, req: &mut Request, _: &Context) -> Result<(), JsonRpcError> {
+        let msg = format!("{} request {}", self.name, req.method);
+        self.log.lock().unwrap().push(msg);
+        Ok(())
+    }
+
+    fn on_response(&self, method: &str, _: &mut Value) {
+        let msg = format!("{} response {}", self.name, method);
+        self.log.lock().unwrap().push(msg);
+    }
+}
+
+// 改写方法名、拒绝admin方法、为响应加标记
+struct Guard;
+
+impl Middleware for Guard {
+    fn on_request(&self, req: &mut Request, _: &Context) -> Result<(), JsonRpcError> {
+        if req.method == "alias" {
+            req.method = "echo".to_string();
+        }
+        if req.method.starts_with("admin.") {
+            return Err(JsonRpcError {
+                code: 403,
+                message: "forbidden".to_string(),
+                data: None,
+            });
+        }
+        Ok(())
+    }
+
+    fn on_response(&self, _: &str, resp: &mut Value) {
+        resp["guarded"] = json!(true);
+    }
+}
+
+#[tokio::test]
+async fn test_server_middleware() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let route = Arc::new(
+        Route::new()
+            .middleware(Recorder {
+                name: "a",
+                log: log.clone(),
+            })
+            .middleware(Guard)
+            .middleware(Recorder {
+                name: "b",
+                log: log.clone(),
+            })
+            .to("echo".to_string(), route_identity)
+            .build()
+            .unwrap(),
+    );
+    let take_log = || std::mem::take(&mut *log.lock().unwrap());
+
+    let resp = route_jsonrpc(
+        route.clone(),
+        r#"{"jsonrpc": "2.0", "method": "alias", "params": [1], "id": 1}"#,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": [1], "id": 1, "guarded": true}),
+        serde_json::from_str::<Value>(&resp).unwrap()
+    );
+    assert_eq!(
+        vec![
+            "a request alias",
+            "b request echo",
+            "b response echo",
+            "a response echo"
+        ],
+        take_log()
+    );
+
+    // 中间件拒绝时不执行之后的中间件与处理函数
+    let resp = route_jsonrpc(
+        route.clone(),
+        r#"{"jsonrpc": "2.0", "method": "admin.reset", "id": 2}"#,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "error": {"code": 403, "message": "forbidden"}, "id": 2}),
+        serde_json::from_str::<Value>(&resp).unwrap()
+    );
+    assert_eq!(
+        vec!["a request admin.reset", "a response admin.reset"],
+        take_log()
+    );
+
+    // 方法不存在的错误响应同样经过中间件
+    let resp = route_jsonrpc(
+        route.clone(),
+        r#"{"jsonrpc": "2.0", "method": "missing", "id": 3}"#,
+    )
+    .await
+    .unwrap();
+    let resp: Value = serde_json::from_str(&resp).unwrap();
+    assert_eq!(-32601, resp["error"]["code"]);
+    assert_eq!(true, resp["guarded"]);
+    take_log();
+
+    // 批量请求逐个经过中间件，通知没有响应
+    let resp = route_jsonrpc(
+        route.clone(),
+        r#"[
+            {"jsonrpc": "2.0", "method": "echo", "params": ["x"], "id": 4},
+            {"jsonrpc": "2.0", "method": "admin.reset", "id": 5},
+            {"jsonrpc": "2.0", "method": "echo", "params": ["y"]}
+        ]"#,
+    )
+    .await
+    .unwrap();
+    let mut resp: Vec<Value> = serde_json::from_str(&resp).unwrap();
+    resp.sort_by_key(|resp| resp["id"].as_u64());
+    assert_eq!(
+        vec![
+            json!({"jsonrpc": "2.0", "result": ["x"], "id": 4, "guarded": true}),
+            json!({"jsonrpc": "2.0", "error": {"code": 403, "message": "forbidden"}, "id": 5}),
+        ],
+        resp
+    );
+    let mut log = take_log();
+    log.sort();
+    assert_eq!(
+        vec![
+            "a request admin.reset",
+            "a request echo",
+            "a request echo",
+            "a response admin.reset",
+            "a response echo",
+            "b request echo",
+            "b request echo",
+            "b response echo",
+        ],
+        log
+    );
+}
+
+// 参数或结果为指定值时在on_request或on_response中panic
+struct Panicker(&'static str);
+
+impl Middleware for Panicker {
+    fn on_request(&self, req: &mut Request, _: &Context) -> Result<(), JsonRpcError> {
+        if req.params == Some(json!([format!("{} request", self.0)])) {
+            panic!("on_request panic");
+        }
+        Ok(())
+    }
+
+    fn on_response(&self, _: &str, resp: &mut Value) {
+        if resp["result"] == json!([format!("{} response", self.0)]) {
+            panic!("on_response panic");
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_server_middleware_panic() {
+    let route = Arc::new(
+        Route::new()
+            .middleware(Panicker("root"))
+            .to("echo".to_string(), route_identity)
+            .mount(
+                "scope".to_string(),
+                Route::new()
+                    .middleware(Panicker("scope"))
+                    .to("echo".to_string(), route_identity),
+            )
+            .build()
+            .unwrap(),
+    );
+
+    // 中间件panic只影响对应的请求
+    let resp = route_jsonrpc(
+        route.clone(),
+        r#"[
+            {"jsonrpc": "2.0", "method": "echo", "params": ["root request"], "id": 1},
+            {"jsonrpc": "2.0", "method": "echo", "params": ["root response"], "id": 2},
+            {"jsonrpc": "2.0", "method": "echo", "params": ["ok"], "id": 3}
+        ]"#,
+    )
+    .await
+    .unwrap();
+    let mut resp: Vec<Value> = serde_json::from_str(&resp).unwrap();
+    resp.sort_by_key(|resp| resp["id"].as_u64());
+    assert_eq!(
+        vec![
+            json!({"jsonrpc": "2.0", "error": JsonRpcError::internal_error(), "id": 1}),
+            json!({"jsonrpc": "2.0", "error": JsonRpcError::internal_error(), "id": 2}),
+            json!({"jsonrpc": "2.0", "result": ["ok"], "id": 3}),
+        ],
+        resp
+    );
+
+    // 子Route的中间件同样隔离
+    for (id, param) in [(4, "scope request"), (5, "scope response")] {
+        let req = json!({"jsonrpc": "2.0", "method": "scope.echo", "params": [param], "id": id});
+        let resp = route_jsonrpc(route.clone(), &req.to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            json!({"jsonrpc": "2.0", "error": JsonRpcError::internal_error(), "id": id}),
+            serde_json::from_str::<Value>(&resp).unwrap()
+        );
+    }
+}
+
+async fn route_scope_name(name: Data<String>, base: Data<u64>) -> Result<String, TestError> {
+    Ok(format!("{}:{}", name.get_ref(), base.get_ref()))
+}
+
+async fn route_call(route: &Arc<Route>, method: &str) -> Value {
+    let req = json!({"jsonrpc": "2.0", "method": method, "id": 1}).to_string();
+    let resp = route_jsonrpc(route.clone(), &req).await.unwrap();
+    serde_json::from_str(&resp).unwrap()
+}
+
+#[tokio::test]
+async fn test_server_mount() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let wallet = Route::new()
+        .data("wallet".to_string())
+        .to("name".to_string(), route_scope_name);
+    let users = Route::new()
+        .data("users".to_string())
+        .to("name".to_string(), route_scope_name);
+    let admin = Route::new()
+        .data("admin".to_string())
+        .middleware(Recorder {
+            name: "admin",
+            log: log.clone(),
+        })
+        .to("name".to_string(), route_scope_name)
+        .mount("users".to_string(), users);
+    let common = Route::new().to("echo".to_string(), route_identity);
+
+    let route = Arc::new(
+        Route::new()
+            .data(7u64)
+            .data("root".to_string())
+            .to("name".to_string(), route_scope_name)
+            .mount("wallet".to_string(), wallet)
+            .mount("admin".to_string(), admin)
+            .mount(String::new(), common)
+            .build()
+            .unwrap(),
+    );
+
+    // 每个子Route使用自己的数据，找不到时使用父Route的数据
+    assert_eq!("root:7", route_call(&route, "name").await["result"]);
+    assert_eq!(
+        "wallet:7",
+        route_call(&route, "wallet.name").await["result"]
+    );
+    assert_eq!("admin:7", route_call(&route, "admin.name").await["result"]);
+    assert_eq!(
+        "users:7",
+        route_call(&route, "admin.users.name").await["result"]
+    );
+    assert_eq!(Value::Null, route_call(&route, "echo").await["result"]);
+    assert_eq!(
+        -32601,
+        route_call(&route, "users.name").await["error"]["code"]
+    );
+
+    // 子Route的中间件只作用于自己的方法
+    assert_eq!(
+        vec![
+            "admin request admin.name",
+            "admin response admin.name",
+            "admin request admin.users.name",
+            "admin response admin.users.name",
+        ],
+        *log.lock().unwrap()
+    );
+}
+
+#[test]
+fn test_server_duplicate_method() {
+    let result = Route::new()
+        .to("echo".to_string(), route_identity)
+        .to("echo".to_string(), route_identity)
+        .build();
+    assert_eq!(
+        RouteError::DuplicateMethod(vec!["echo".to_string()]),
+        result.err().unwrap()
+    );
+
+    // 挂载时与已有方法冲突，或者子Route自身有重复
+    let wallet = Route::new()
+        .to("echo".to_string(), route_identity)
+        .to("name".to_string(), route_identity)
+        .to("name".to_string(), route_identity);
+    let result = Route::new()
+        .to("wallet.echo".to_string(), route_identity)
+        .mount("wallet".to_string(), wallet)
+        .build();
+    let err = result.err().unwrap();
+    assert_eq!(
+        RouteError::DuplicateMethod(vec!["wallet.echo".to_string(), "wallet.name".to_string()]),
+        err
+    );
+    assert_eq!(
+        "method [wallet.echo, wallet.name] registered more than once",
+        err.to_string()
+    );
+
+    // 子Route缺少的数据以完整方法名报告
+    let wallet = Route::new().to("name".to_string(), route_scope_name);
+    let result = Route::new()
+        .data(7u64)
+        .mount("wallet".to_string(), wallet)
+        .build();
+    assert_eq!(
+        RouteError::MissingData(vec![(
+            "wallet.name".to_string(),
+            vec!["alloc::string::String"]
+        )]),
+        result.err().unwrap()
+    );
+}
+
+// 只在服务端使用，不生成依赖jsonrpc-websocket的客户端
+#[jsonrpc_derive::rpc(namespace = "server", client = false)]
+pub trait ServerApi {
+    async fn double(&self, value: u64) -> Result<u64, TestError>;
+}
+
+struct Doubler;
+
+impl ServerApi for Doubler {
+    async fn double(&self, value: u64) -> Result<u64, TestError> {
+        Ok(value * 2)
+    }
+}
+
+#[tokio::test]
+async fn test_server_rpc_trait_without_client() {
+    let route = Arc::new(Doubler.into_route(Route::new()).build().unwrap());
+    let resp = route_jsonrpc(
+        route,
+        r#"{"jsonrpc": "2.0", "method": "server.double", "params": [2], "id": 1}"#,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        json!({"jsonrpc": "2.0", "result": 4, "id": 1}),
+        serde_json::from_str::<Value>(&resp).unwrap()
+    );
+}